@@ -0,0 +1,284 @@
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+use bevy_xpbd_3d::prelude::LinearVelocity;
+use crate::movement::MovementPath;
+
+/// Local collision avoidance between units, using reciprocal velocity obstacles (RVO).
+///
+/// `movement_system` picks the velocity that follows the path, and `avoidance_system` then
+/// replaces it with the closest sampled velocity that doesn't run into nearby units soon.
+#[derive(Component, Reflect)]
+pub struct Avoidance {
+    pub radius: f32,
+    velocity: Vec2,
+}
+
+impl Avoidance {
+    pub fn new(radius: f32) -> Self {
+        Self {
+            radius,
+            velocity: Vec2::ZERO,
+        }
+    }
+}
+
+#[derive(Resource, Reflect)]
+pub struct AvoidanceSettings {
+    /// Units further apart than this are ignored.
+    pub neighbor_distance: f32,
+    /// Collisions further in the future than this many seconds are ignored.
+    pub time_horizon: f32,
+    /// How strongly to prefer avoiding an imminent collision over keeping the preferred velocity.
+    pub aggressiveness: f32,
+    /// Number of headings sampled on each side of the preferred velocity.
+    pub angle_samples: usize,
+    /// Maximum deviation from the preferred heading, in radians.
+    pub max_angle: f32,
+    pub speed_samples: usize,
+}
+
+impl Default for AvoidanceSettings {
+    fn default() -> Self {
+        Self {
+            neighbor_distance: 10.,
+            time_horizon: 3.,
+            aggressiveness: 2.,
+            angle_samples: 8,
+            max_angle: std::f32::consts::FRAC_PI_2,
+            speed_samples: 4,
+        }
+    }
+}
+
+/// Snapshot of a unit on the ground plane (`x`, `z`).
+#[derive(Clone, Copy, Debug)]
+pub struct Agent {
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub preferred_velocity: Vec2,
+    pub radius: f32,
+    /// Whether the agent also avoids, so each only has to take half the responsibility.
+    pub reciprocal: bool,
+}
+
+/// Picks the velocity closest to the agent's preferred one that avoids imminent collisions.
+///
+/// Candidates are visited from the preferred heading outwards, right side first, so that two
+/// units meeting head-on both veer to their own right instead of mirroring each other.
+pub fn avoid(agent: &Agent, neighbors: &[Agent], settings: &AvoidanceSettings) -> Vec2 {
+    let max_speed = agent.preferred_velocity.length();
+    if max_speed <= f32::EPSILON || neighbors.is_empty() {
+        return agent.preferred_velocity;
+    }
+    let heading = agent.preferred_velocity / max_speed;
+    let mut best = Vec2::ZERO;
+    let mut best_penalty = f32::INFINITY;
+    for candidate in candidates(heading, max_speed, settings) {
+        let time_to_collision = neighbors.iter()
+            .map(|neighbor| {
+                let relative_velocity = if neighbor.reciprocal {
+                    2. * candidate - agent.velocity - neighbor.velocity
+                } else {
+                    candidate - neighbor.velocity
+                };
+                time_to_collision(
+                    neighbor.position - agent.position,
+                    relative_velocity,
+                    agent.radius + neighbor.radius,
+                )
+            })
+            .fold(f32::INFINITY, f32::min);
+        let collision_penalty = if time_to_collision > settings.time_horizon {
+            0.
+        } else {
+            settings.aggressiveness / time_to_collision
+        };
+        let penalty = collision_penalty + (candidate - agent.preferred_velocity).length();
+        if penalty < best_penalty {
+            best_penalty = penalty;
+            best = candidate;
+        }
+    }
+    best
+}
+
+fn candidates(heading: Vec2, max_speed: f32, settings: &AvoidanceSettings) -> Vec<Vec2> {
+    let angle_step = settings.max_angle / settings.angle_samples.max(1) as f32;
+    let mut candidates = Vec::with_capacity(settings.speed_samples * (2 * settings.angle_samples + 1) + 1);
+    for speed_step in 0..settings.speed_samples {
+        let speed = max_speed * (settings.speed_samples - speed_step) as f32 / settings.speed_samples as f32;
+        candidates.push(heading * speed);
+        for angle_step_index in 1..=settings.angle_samples {
+            let angle = angle_step * angle_step_index as f32;
+            candidates.push(Vec2::from_angle(-angle).rotate(heading) * speed);
+            candidates.push(Vec2::from_angle(angle).rotate(heading) * speed);
+        }
+    }
+    candidates.push(Vec2::ZERO);
+    candidates
+}
+
+/// Seconds until a disc at `relative_position` with combined `radius` is hit when moving at
+/// `relative_velocity`, or infinity if it never is.
+fn time_to_collision(relative_position: Vec2, relative_velocity: Vec2, radius: f32) -> f32 {
+    let closing = relative_position.dot(relative_velocity);
+    let distance_squared = relative_position.length_squared() - radius * radius;
+    if distance_squared < 0. {
+        // Already overlapping: only moving apart is collision free.
+        return if closing > 0. { 0. } else { f32::INFINITY };
+    }
+    let speed_squared = relative_velocity.length_squared();
+    let discriminant = closing * closing - speed_squared * distance_squared;
+    if closing <= 0. || discriminant < 0. {
+        return f32::INFINITY;
+    }
+    (closing - discriminant.sqrt()) / speed_squared
+}
+
+pub(crate) fn avoidance_system(
+    settings: Res<AvoidanceSettings>,
+    mut units_q: Query<(Entity, &mut Avoidance, &Transform, &mut LinearVelocity, Option<&MovementPath>)>,
+) {
+    let agents: Vec<(Entity, Agent)> = units_q.iter()
+        .map(|(entity, avoidance, transform, velocity, path)| (entity, Agent {
+            position: transform.translation.xz(),
+            velocity: avoidance.velocity,
+            preferred_velocity: velocity.0.xz(),
+            radius: avoidance.radius,
+            reciprocal: path.is_some_and(|path| !path.is_empty()),
+        }))
+        .collect();
+    let neighbor_distance_squared = settings.neighbor_distance * settings.neighbor_distance;
+
+    for (entity, agent) in agents.iter() {
+        let Ok((_, mut avoidance, _, mut velocity, _)) = units_q.get_mut(*entity) else {
+            continue;
+        };
+        if !agent.reciprocal {
+            avoidance.velocity = agent.preferred_velocity;
+            continue;
+        }
+        let neighbors: Vec<Agent> = agents.iter()
+            .filter(|(other, neighbor)| other != entity
+                && neighbor.position.distance_squared(agent.position) <= neighbor_distance_squared)
+            .map(|(_, neighbor)| *neighbor)
+            .collect();
+        let avoiding = avoid(agent, &neighbors, &settings);
+        avoidance.velocity = avoiding;
+        velocity.0 = Vec3::new(avoiding.x, velocity.0.y, avoiding.y);
+    }
+}
+
+#[cfg(test)]
+mod avoidance_test {
+    use std::time::Duration;
+    use bevy::prelude::*;
+    use bevy::time::TimeUpdateStrategy;
+    use bevy_xpbd_3d::prelude::{LinearVelocity, Rotation};
+    use crate::avoidance::{avoid, Agent, Avoidance, AvoidanceSettings};
+    use crate::game_state::{AppState, MatchState};
+    use crate::movement::{MovementPath, MovementPlugin, MovementStats};
+
+    fn step_towards(agent: &mut Agent, goal: Vec2, speed: f32) {
+        let to_goal = goal - agent.position;
+        agent.preferred_velocity = to_goal.clamp_length_max(speed);
+    }
+
+    #[test]
+    fn it_keeps_preferred_velocity_without_neighbors() {
+        let agent = Agent {
+            position: Vec2::ZERO,
+            velocity: Vec2::ZERO,
+            preferred_velocity: Vec2::new(0., 5.),
+            radius: 0.5,
+            reciprocal: true,
+        };
+        assert_eq!(avoid(&agent, &[], &AvoidanceSettings::default()), Vec2::new(0., 5.));
+    }
+
+    #[test]
+    fn it_lets_two_units_pass_each_other_head_on() {
+        let settings = AvoidanceSettings::default();
+        let speed = 5.;
+        let delta = 1. / 60.;
+        let goals = [Vec2::new(0., 10.), Vec2::new(0., -10.)];
+        let mut agents = [
+            Agent {
+                position: Vec2::new(0., -10.),
+                velocity: Vec2::ZERO,
+                preferred_velocity: Vec2::ZERO,
+                radius: 0.5,
+                reciprocal: true,
+            },
+            Agent {
+                position: Vec2::new(0., 10.),
+                velocity: Vec2::ZERO,
+                preferred_velocity: Vec2::ZERO,
+                radius: 0.5,
+                reciprocal: true,
+            },
+        ];
+        let mut closest = f32::INFINITY;
+        for _ in 0..600 {
+            for (agent, goal) in agents.iter_mut().zip(goals) {
+                step_towards(agent, goal, speed);
+            }
+            let velocities = [
+                avoid(&agents[0], &[agents[1]], &settings),
+                avoid(&agents[1], &[agents[0]], &settings),
+            ];
+            for (agent, velocity) in agents.iter_mut().zip(velocities) {
+                agent.velocity = velocity;
+                agent.position += velocity * delta;
+            }
+            closest = closest.min(agents[0].position.distance(agents[1].position));
+        }
+        assert!(closest >= 1., "units overlapped, closest distance was {}", closest);
+        for (agent, goal) in agents.iter().zip(goals) {
+            assert!(agent.position.distance(goal) < 0.1, "unit stopped at {}", agent.position);
+        }
+    }
+
+    // Stands in for the physics step, which moves bodies by their velocity after the movement systems.
+    fn integrate_velocity(time: Res<Time>, mut bodies_q: Query<(&mut Transform, &LinearVelocity)>) {
+        for (mut transform, velocity) in bodies_q.iter_mut() {
+            transform.translation += velocity.0 * time.delta_seconds();
+        }
+    }
+
+    #[test]
+    fn it_steers_two_moving_units_past_each_other() {
+        let mut app = App::new();
+        app
+            .add_state::<AppState>()
+            .add_state::<MatchState>()
+            .insert_resource(NextState(Some(AppState::InGame)))
+            .add_plugins((MinimalPlugins, MovementPlugin))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(1. / 60.)))
+            .add_systems(PostUpdate, integrate_velocity);
+        let radius = 0.5;
+        let goals = [Vec3::new(0., 0., 10.), Vec3::new(0., 0., -10.)];
+        let units = goals.map(|goal| app.world.spawn((
+            TransformBundle::from_transform(Transform::from_translation(-goal)),
+            MovementPath::new(vec![goal]),
+            MovementStats::default(),
+            Avoidance::new(radius),
+            Rotation::default(),
+            LinearVelocity::default(),
+        )).id());
+
+        let mut closest = f32::INFINITY;
+        for _ in 0..600 {
+            app.update();
+            let [a, b] = units.map(|unit| app.world.get::<Transform>(unit).unwrap().translation);
+            closest = closest.min(a.distance(b));
+        }
+
+        assert!(closest >= 2. * radius, "units overlapped, closest distance was {}", closest);
+        for (unit, goal) in units.into_iter().zip(goals) {
+            let position = app.world.get::<Transform>(unit).unwrap().translation;
+            assert!(app.world.get::<MovementPath>(unit).unwrap().is_empty(), "unit stopped at {}", position);
+            assert!(position.distance(goal) <= MovementStats::default().arrival_radius + 0.1, "unit stopped at {}", position);
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::resources::Cost;
+
+/// Structures the player can place.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Reflect, Serialize, Deserialize)]
+pub enum BuildingKind {
+    Depot,
+    Barracks,
+}
+
+impl BuildingKind {
+    /// Order of the placement hotkeys.
+    pub const ALL: [BuildingKind; 2] = [BuildingKind::Depot, BuildingKind::Barracks];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BuildingKind::Depot => "Depot",
+            BuildingKind::Barracks => "Barracks",
+        }
+    }
+
+    /// Resources paid when the building is placed.
+    pub fn cost(&self) -> Cost {
+        match self {
+            BuildingKind::Depot => Cost::gold(150),
+            BuildingKind::Barracks => Cost::gold(100),
+        }
+    }
+
+    /// Full extents of the building's box.
+    pub fn size(&self) -> Vec3 {
+        match self {
+            BuildingKind::Depot => Vec3::new(3., 3., 3.),
+            BuildingKind::Barracks => Vec3::new(4., 2.5, 4.),
+        }
+    }
+
+    pub fn supply_provided(&self) -> u32 {
+        match self {
+            BuildingKind::Depot => 10,
+            BuildingKind::Barracks => 0,
+        }
+    }
+
+    /// Names of the unit types the building trains.
+    pub fn trainable(&self) -> Vec<String> {
+        match self {
+            BuildingKind::Depot => vec!["Worker".to_string()],
+            BuildingKind::Barracks => vec!["Soldier".to_string()],
+        }
+    }
+
+    pub fn footprint(&self) -> Footprint {
+        let size = self.size();
+        Footprint::new(size.x / 2., size.z / 2.)
+    }
+}
+
+#[derive(Component, Reflect)]
+pub struct Building(pub BuildingKind);
+
+/// Ground area an obstacle covers, used to keep placed buildings from overlapping it.
+#[derive(Component, Clone, Copy, Debug, Reflect)]
+pub struct Footprint {
+    pub half_extents: Vec2,
+}
+
+impl Footprint {
+    pub fn new(half_width: f32, half_depth: f32) -> Self {
+        Self { half_extents: Vec2::new(half_width, half_depth) }
+    }
+
+    /// Area covered on the ground plane (x, z) when centered at `center`.
+    pub fn rect(&self, center: Vec3) -> Rect {
+        Rect::from_center_half_size(Vec2::new(center.x, center.z), self.half_extents)
+    }
+}
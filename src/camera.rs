@@ -1,40 +1,519 @@
 use bevy::app::{App, Plugin, Startup};
+use bevy::input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel};
 use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use crate::game_state::AppState;
+use crate::input::{ActionState, CursorPosition, InputAction};
+use crate::map::MapSize;
+use crate::selection::Selected;
+
+/// How far behind the focus point the camera sits, relative to its height.
+const BACK_RATIO: f32 = 0.875;
+/// Number of saved camera positions.
+pub const BOOKMARK_SLOTS: usize = 4;
 
 pub struct MyCameraPlugin;
 
 impl Plugin for MyCameraPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, spawn_main_camera);
+        app
+            .init_resource::<CameraSettings>()
+            .init_resource::<CameraBookmarks>()
+            .add_event::<CameraCommand>()
+            .register_type::<CameraController>()
+            .add_systems(Startup, spawn_main_camera)
+            .add_systems(
+                Update,
+                (
+                    camera_input_system,
+                    camera_command_system,
+                    camera_follow_system,
+                    camera_pan_system,
+                    camera_edge_scroll_system.run_if(in_state(AppState::InGame)),
+                    camera_zoom_system,
+                    camera_rotate_system,
+                    apply_camera_controller,
+                ).chain());
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct CameraSettings {
+    /// Ground units per second at the default height; panning speeds up as the camera zooms out.
+    pub pan_speed: f32,
+    /// Logical pixels from the window's border where the cursor scrolls the camera.
+    pub edge_margin: f32,
+    /// Height change per scroll wheel line.
+    pub zoom_step: f32,
+    pub min_height: f32,
+    pub max_height: f32,
+    /// Radians per second while a rotate key is held.
+    pub rotate_speed: f32,
+    /// Radians per logical pixel of mouse movement while drag-rotating.
+    pub drag_rotate_speed: f32,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            pan_speed: 30.,
+            edge_margin: 10.,
+            zoom_step: 4.,
+            min_height: 10.,
+            max_height: 80.,
+            rotate_speed: 2.,
+            drag_rotate_speed: 0.01,
+        }
+    }
+}
+
+/// Where the camera looks at on the ground, from how high and from which direction.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Reflect)]
+pub struct CameraController {
+    pub focus: Vec3,
+    pub height: f32,
+    /// Radians around Y; zero looks towards +Z.
+    pub yaw: f32,
+}
+
+impl Default for CameraController {
+    fn default() -> Self {
+        Self {
+            focus: Vec3::new(0., 0., 10.),
+            height: 40.,
+            yaw: 0.,
+        }
+    }
+}
+
+impl CameraController {
+    pub fn transform(&self) -> Transform {
+        let offset = Quat::from_rotation_y(self.yaw) * Vec3::new(0., self.height, -self.height * BACK_RATIO);
+        Transform::from_translation(self.focus + offset).looking_at(self.focus, Vec3::Y)
+    }
+
+    /// Ground direction the camera faces.
+    pub fn forward(&self) -> Vec3 {
+        Quat::from_rotation_y(self.yaw) * Vec3::Z
+    }
+
+    /// Ground direction to the right of the screen.
+    pub fn right(&self) -> Vec3 {
+        Quat::from_rotation_y(self.yaw) * Vec3::NEG_X
+    }
+}
+
+/// Request to move the camera, sent by hotkeys or by anything else (minimap, alerts).
+#[derive(Event, Clone, Copy, Debug, PartialEq)]
+pub enum CameraCommand {
+    /// Looks at a ground position, keeping the height and rotation.
+    CenterOn(Vec3),
+    /// Keeps an entity centered until the camera is panned, or stops following with `None`.
+    Follow(Option<Entity>),
+    SaveBookmark(usize),
+    RecallBookmark(usize),
+}
+
+/// Entity the camera keeps centered.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct CameraFollow(pub Entity);
+
+/// Camera positions saved with `CameraCommand::SaveBookmark`.
+#[derive(Resource, Default)]
+pub struct CameraBookmarks {
+    slots: [Option<CameraController>; BOOKMARK_SLOTS],
+}
+
+impl CameraBookmarks {
+    pub fn get(&self, slot: usize) -> Option<&CameraController> {
+        self.slots.get(slot).and_then(Option::as_ref)
     }
 }
 
 fn spawn_main_camera(mut commands: Commands) {
+    let controller = CameraController::default();
     commands.spawn((
         MainCamera,
         Camera3dBundle {
-            transform: Transform::from_xyz(0., 40., -25.)
-                .looking_at(Vec3::new(0., 0., 10.), Vec3::Y),
+            transform: controller.transform(),
             ..default()
         },
+        controller,
     ));
 }
 
 #[derive(Component)]
 pub struct MainCamera;
 
+// Bookmark keys recall a saved position, or save it while the assign modifier is held; follow toggles.
+fn camera_input_system(
+    actions: Res<ActionState>,
+    selected_q: Query<Entity, With<Selected>>,
+    follow_q: Query<(), (With<MainCamera>, With<CameraFollow>)>,
+    mut camera_commands: EventWriter<CameraCommand>,
+) {
+    for slot in 0..BOOKMARK_SLOTS {
+        if !actions.just_pressed(InputAction::Bookmark(slot)) {
+            continue;
+        }
+        camera_commands.send(if actions.pressed(InputAction::Assign) {
+            CameraCommand::SaveBookmark(slot)
+        } else {
+            CameraCommand::RecallBookmark(slot)
+        });
+    }
+    if actions.just_pressed(InputAction::FollowSelection) {
+        let target = if follow_q.is_empty() { selected_q.iter().next() } else { None };
+        camera_commands.send(CameraCommand::Follow(target));
+    }
+}
+
+// Camera systems use the raw delta so the camera keeps moving while the game is paused or sped up.
+fn camera_pan_system(
+    mut commands: Commands,
+    actions: Res<ActionState>,
+    settings: Res<CameraSettings>,
+    time: Res<Time>,
+    mut camera_q: Query<(Entity, &mut CameraController), With<MainCamera>>,
+) {
+    let mut direction = Vec2::ZERO;
+    if actions.pressed(InputAction::PanForward) {
+        direction.y += 1.;
+    }
+    if actions.pressed(InputAction::PanBack) {
+        direction.y -= 1.;
+    }
+    if actions.pressed(InputAction::PanRight) {
+        direction.x += 1.;
+    }
+    if actions.pressed(InputAction::PanLeft) {
+        direction.x -= 1.;
+    }
+    pan_camera(&mut commands, &settings, time.raw_delta_seconds(), &mut camera_q, direction);
+}
+
+// Scrolls while the cursor rests on the window's border, only during a match so menus don't move the camera.
+fn camera_edge_scroll_system(
+    mut commands: Commands,
+    cursor: Res<CursorPosition>,
+    settings: Res<CameraSettings>,
+    time: Res<Time>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    mut camera_q: Query<(Entity, &mut CameraController), With<MainCamera>>,
+) {
+    let (Ok(window), Some(screen)) = (window_q.get_single(), cursor.screen) else {
+        return;
+    };
+    if !window.focused {
+        return;
+    }
+    let mut direction = Vec2::ZERO;
+    if screen.x < settings.edge_margin {
+        direction.x -= 1.;
+    } else if screen.x > window.width() - settings.edge_margin {
+        direction.x += 1.;
+    }
+    if screen.y < settings.edge_margin {
+        direction.y += 1.;
+    } else if screen.y > window.height() - settings.edge_margin {
+        direction.y -= 1.;
+    }
+    pan_camera(&mut commands, &settings, time.raw_delta_seconds(), &mut camera_q, direction);
+}
+
+// Moves the focus along the ground, faster when zoomed out, and stops following.
+fn pan_camera(
+    commands: &mut Commands,
+    settings: &CameraSettings,
+    delta_seconds: f32,
+    camera_q: &mut Query<(Entity, &mut CameraController), With<MainCamera>>,
+    direction: Vec2,
+) {
+    if direction == Vec2::ZERO {
+        return;
+    }
+    let Ok((camera, mut controller)) = camera_q.get_single_mut() else {
+        return;
+    };
+    let speed = settings.pan_speed * controller.height / CameraController::default().height;
+    let movement = (controller.right() * direction.x + controller.forward() * direction.y).normalize()
+        * speed * delta_seconds;
+    controller.focus += movement;
+    commands.entity(camera).remove::<CameraFollow>();
+}
+
+fn camera_zoom_system(
+    cursor: Res<CursorPosition>,
+    settings: Res<CameraSettings>,
+    mut wheel: EventReader<MouseWheel>,
+    mut camera_q: Query<&mut CameraController, With<MainCamera>>,
+) {
+    let lines: f32 = wheel.iter()
+        .map(|event| match event.unit {
+            MouseScrollUnit::Line => event.y,
+            MouseScrollUnit::Pixel => event.y / 50.,
+        })
+        .sum();
+    // Scrolling over the UI is left to the UI.
+    if lines == 0. || cursor.over_ui {
+        return;
+    }
+    let Ok(mut controller) = camera_q.get_single_mut() else {
+        return;
+    };
+    controller.height = (controller.height - lines * settings.zoom_step).clamp(settings.min_height, settings.max_height);
+}
+
+fn camera_rotate_system(
+    actions: Res<ActionState>,
+    settings: Res<CameraSettings>,
+    time: Res<Time>,
+    mut motion: EventReader<MouseMotion>,
+    mut camera_q: Query<&mut CameraController, With<MainCamera>>,
+) {
+    let dragged: f32 = motion.iter().map(|event| event.delta.x).sum();
+    let Ok(mut controller) = camera_q.get_single_mut() else {
+        return;
+    };
+    let mut turn = 0.;
+    if actions.pressed(InputAction::RotateCameraLeft) {
+        turn -= settings.rotate_speed * time.raw_delta_seconds();
+    }
+    if actions.pressed(InputAction::RotateCameraRight) {
+        turn += settings.rotate_speed * time.raw_delta_seconds();
+    }
+    if actions.pressed(InputAction::DragRotateCamera) {
+        turn += dragged * settings.drag_rotate_speed;
+    }
+    if turn != 0. {
+        controller.yaw += turn;
+    }
+}
+
+fn camera_command_system(
+    mut commands: Commands,
+    mut camera_commands: EventReader<CameraCommand>,
+    mut bookmarks: ResMut<CameraBookmarks>,
+    mut camera_q: Query<(Entity, &mut CameraController), With<MainCamera>>,
+) {
+    let Ok((camera, mut controller)) = camera_q.get_single_mut() else {
+        camera_commands.clear();
+        return;
+    };
+    for command in camera_commands.iter() {
+        match *command {
+            CameraCommand::CenterOn(position) => {
+                controller.focus = Vec3::new(position.x, 0., position.z);
+                commands.entity(camera).remove::<CameraFollow>();
+            }
+            CameraCommand::Follow(Some(target)) => {
+                commands.entity(camera).insert(CameraFollow(target));
+            }
+            CameraCommand::Follow(None) => {
+                commands.entity(camera).remove::<CameraFollow>();
+            }
+            CameraCommand::SaveBookmark(slot) => {
+                if let Some(bookmark) = bookmarks.slots.get_mut(slot) {
+                    *bookmark = Some(*controller);
+                }
+            }
+            CameraCommand::RecallBookmark(slot) => {
+                if let Some(bookmark) = bookmarks.get(slot) {
+                    *controller = *bookmark;
+                    commands.entity(camera).remove::<CameraFollow>();
+                }
+            }
+        }
+    }
+}
+
+fn camera_follow_system(
+    mut commands: Commands,
+    targets_q: Query<&Transform>,
+    mut camera_q: Query<(Entity, &mut CameraController, &CameraFollow), With<MainCamera>>,
+) {
+    let Ok((camera, mut controller, follow)) = camera_q.get_single_mut() else {
+        return;
+    };
+    let Ok(target) = targets_q.get(follow.0) else {
+        commands.entity(camera).remove::<CameraFollow>();
+        return;
+    };
+    let focus = Vec3::new(target.translation.x, 0., target.translation.z);
+    if controller.focus != focus {
+        controller.focus = focus;
+    }
+}
+
+// Keeps the focus point over the map and moves the camera to match its controller.
+fn apply_camera_controller(
+    map_size: Option<Res<MapSize>>,
+    mut camera_q: Query<(&mut CameraController, &mut Transform), (With<MainCamera>, Changed<CameraController>)>,
+) {
+    let Ok((mut controller, mut transform)) = camera_q.get_single_mut() else {
+        return;
+    };
+    if let Some(map_size) = map_size {
+        let half_size = map_size.0 / 2.;
+        let focus = Vec3::new(
+            controller.focus.x.clamp(-half_size, half_size),
+            0.,
+            controller.focus.z.clamp(-half_size, half_size));
+        if controller.focus != focus {
+            controller.focus = focus;
+        }
+    }
+    *transform = controller.transform();
+}
+
 #[cfg(test)]
 mod camera_test {
+    use std::time::Duration;
     use bevy::core_pipeline::core_2d::Core2dPlugin;
+    use bevy::input::ButtonState;
+    use bevy::input::keyboard::KeyboardInput;
+    use bevy::input::mouse::{MouseScrollUnit, MouseWheel};
+    use bevy::input::InputPlugin;
     use bevy::prelude::*;
-    use crate::camera::{MainCamera, MyCameraPlugin};
+    use bevy::time::TimeUpdateStrategy;
+    use crate::camera::{CameraCommand, CameraController, CameraSettings, MainCamera, MyCameraPlugin};
+    use crate::game_state::AppState;
+    use crate::input::{CursorPosition, InputActionsPlugin, InputBindings};
+    use crate::map::MapSize;
 
-    #[test]
-    fn it_spawns_main_camera() {
+    fn setup() -> App {
         let mut app = App::new();
-        app.add_plugins((Core2dPlugin, MyCameraPlugin));
+        app
+            .add_plugins((MinimalPlugins, Core2dPlugin, InputPlugin, InputActionsPlugin, MyCameraPlugin))
+            .add_state::<AppState>()
+            .insert_resource(InputBindings::default())
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(100)));
         app.update();
+        app
+    }
+
+    fn send_key(app: &mut App, key_code: KeyCode, state: ButtonState) {
+        app.world.send_event(KeyboardInput {
+            scan_code: 0,
+            key_code: Some(key_code),
+            state,
+            window: Entity::PLACEHOLDER,
+        });
+    }
+
+    fn camera(app: &mut App) -> (CameraController, Transform) {
+        let (controller, transform) = app.world.query_filtered::<(&CameraController, &Transform), With<MainCamera>>()
+            .single(&app.world);
+        (*controller, *transform)
+    }
+
+    #[test]
+    fn it_spawns_main_camera() {
+        let mut app = setup();
         let result = app.world.query::<(&MainCamera, &Camera)>().get_single(&app.world);
         assert!(result.is_ok())
     }
+
+    #[test]
+    fn it_pans_forward_while_key_is_held() {
+        let mut app = setup();
+        let (_, before) = camera(&mut app);
+        send_key(&mut app, KeyCode::W, ButtonState::Pressed);
+        app.update();
+        app.update();
+        let (_, after) = camera(&mut app);
+        assert!(after.translation.z > before.translation.z);
+        assert_eq!(after.translation.x, before.translation.x);
+        assert_eq!(after.translation.y, before.translation.y);
+    }
+
+    #[test]
+    fn it_zooms_with_the_scroll_wheel_within_limits() {
+        let mut app = setup();
+        app.world.send_event(MouseWheel { unit: MouseScrollUnit::Line, x: 0., y: 1., window: Entity::PLACEHOLDER });
+        app.update();
+        let (controller, transform) = camera(&mut app);
+        assert_eq!(controller.height, 36.);
+        assert_eq!(transform.translation.y, 36.);
+
+        app.world.send_event(MouseWheel { unit: MouseScrollUnit::Line, x: 0., y: 100., window: Entity::PLACEHOLDER });
+        app.update();
+        assert_eq!(camera(&mut app).0.height, app.world.resource::<CameraSettings>().min_height);
+    }
+
+    #[test]
+    fn it_ignores_the_scroll_wheel_over_the_ui() {
+        let mut app = setup();
+        app.world.spawn(Interaction::Hovered);
+        app.world.send_event(MouseWheel { unit: MouseScrollUnit::Line, x: 0., y: 1., window: Entity::PLACEHOLDER });
+        app.update();
+        assert!(app.world.resource::<CursorPosition>().over_ui);
+        assert_eq!(camera(&mut app).0.height, CameraController::default().height);
+    }
+
+    #[test]
+    fn it_rotates_around_the_focus_point() {
+        let mut app = setup();
+        let (before, _) = camera(&mut app);
+        send_key(&mut app, KeyCode::E, ButtonState::Pressed);
+        app.update();
+        app.update();
+        let (after, transform) = camera(&mut app);
+        assert!(after.yaw > before.yaw);
+        assert_eq!(after.focus, before.focus);
+        let to_focus = after.focus - transform.translation;
+        assert!(transform.forward().abs_diff_eq(to_focus.normalize(), 1e-4));
+    }
+
+    #[test]
+    fn it_keeps_the_focus_inside_the_map() {
+        let mut app = setup();
+        app.insert_resource(MapSize(20.));
+        app.world.query_filtered::<&mut CameraController, With<MainCamera>>()
+            .single_mut(&mut app.world)
+            .focus = Vec3::new(50., 0., -50.);
+        app.update();
+        assert_eq!(camera(&mut app).0.focus, Vec3::new(10., 0., -10.));
+    }
+
+    #[test]
+    fn it_recalls_saved_bookmarks() {
+        let mut app = setup();
+        app.world.send_event(CameraCommand::SaveBookmark(1));
+        app.update();
+        let (saved, _) = camera(&mut app);
+
+        app.world.send_event(CameraCommand::CenterOn(Vec3::new(20., 0., 5.)));
+        app.update();
+        assert_eq!(camera(&mut app).0.focus, Vec3::new(20., 0., 5.));
+
+        app.world.send_event(CameraCommand::RecallBookmark(1));
+        app.update();
+        assert_eq!(camera(&mut app).0, saved);
+    }
+
+    #[test]
+    fn it_follows_a_unit_until_panned() {
+        let mut app = setup();
+        let unit = app.world.spawn(Transform::from_xyz(5., 1., 5.)).id();
+        app.world.send_event(CameraCommand::Follow(Some(unit)));
+        app.update();
+        app.update();
+        assert_eq!(camera(&mut app).0.focus, Vec3::new(5., 0., 5.));
+
+        app.world.entity_mut(unit).insert(Transform::from_xyz(8., 1., 5.));
+        app.update();
+        app.update();
+        assert_eq!(camera(&mut app).0.focus, Vec3::new(8., 0., 5.));
+
+        send_key(&mut app, KeyCode::A, ButtonState::Pressed);
+        app.update();
+        send_key(&mut app, KeyCode::A, ButtonState::Released);
+        app.update();
+        let panned = camera(&mut app).0.focus;
+        app.world.entity_mut(unit).insert(Transform::from_xyz(-8., 1., 5.));
+        app.update();
+        app.update();
+        assert_eq!(camera(&mut app).0.focus, panned);
+    }
 }
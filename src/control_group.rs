@@ -0,0 +1,152 @@
+use bevy::prelude::*;
+use crate::camera::CameraCommand;
+use crate::game_state::match_running;
+use crate::input::{ActionState, InputAction};
+use crate::selection::{SelectEvent, Selected, SelectionMode};
+
+pub const CONTROL_GROUPS: usize = 10;
+/// Seconds between two presses of the same control group that count as a double tap.
+const DOUBLE_TAP_TIME: f64 = 0.3;
+
+pub struct ControlGroupPlugin;
+
+impl Plugin for ControlGroupPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<ControlGroups>()
+            .add_systems(Update, control_group_system.run_if(match_running));
+    }
+}
+
+/// Entities saved under each control group, and the last group recalled for detecting double taps.
+#[derive(Resource, Default)]
+pub struct ControlGroups {
+    groups: [Vec<Entity>; CONTROL_GROUPS],
+    last_recall: Option<(usize, f64)>,
+}
+
+impl ControlGroups {
+    pub fn get(&self, index: usize) -> &[Entity] {
+        self.groups.get(index).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    pub fn assign(&mut self, index: usize, entities: Vec<Entity>) {
+        if let Some(group) = self.groups.get_mut(index) {
+            *group = entities;
+        }
+    }
+}
+
+// Assign saves the selection to a group; pressing a group selects it, and pressing it again quickly centers the camera on it.
+fn control_group_system(
+    actions: Res<ActionState>,
+    time: Res<Time>,
+    mut groups: ResMut<ControlGroups>,
+    selected_q: Query<Entity, With<Selected>>,
+    transforms_q: Query<&Transform>,
+    mut select_events: EventWriter<SelectEvent>,
+    mut camera_commands: EventWriter<CameraCommand>,
+) {
+    let Some(index) = (0..CONTROL_GROUPS).find(|index| actions.just_pressed(InputAction::ControlGroup(*index))) else {
+        return;
+    };
+    if actions.pressed(InputAction::Assign) {
+        let mut selected: Vec<Entity> = selected_q.iter().collect();
+        selected.sort();
+        groups.assign(index, selected);
+        groups.last_recall = None;
+        return;
+    }
+
+    let alive: Vec<Entity> = groups.get(index).iter().copied()
+        .filter(|entity| transforms_q.contains(*entity))
+        .collect();
+    let now = time.raw_elapsed_seconds_f64();
+    let double_tap = groups.last_recall
+        .is_some_and(|(last_index, at)| last_index == index && now - at <= DOUBLE_TAP_TIME);
+    groups.last_recall = Some((index, now));
+    if double_tap && !alive.is_empty() {
+        let center = alive.iter()
+            .filter_map(|entity| transforms_q.get(*entity).ok())
+            .map(|transform| transform.translation)
+            .sum::<Vec3>() / alive.len() as f32;
+        camera_commands.send(CameraCommand::CenterOn(center));
+    }
+    select_events.send(SelectEvent { entities: alive.clone(), mode: SelectionMode::Replace });
+    groups.assign(index, alive);
+}
+
+#[cfg(test)]
+mod control_group_test {
+    use std::time::Duration;
+    use bevy::input::ButtonState;
+    use bevy::input::keyboard::KeyboardInput;
+    use bevy::input::InputPlugin;
+    use bevy::prelude::*;
+    use bevy::time::TimeUpdateStrategy;
+    use crate::camera::CameraCommand;
+    use crate::control_group::{ControlGroupPlugin, ControlGroups};
+    use crate::game_state::{AppState, MatchState};
+    use crate::input::{InputActionsPlugin, InputBindings};
+    use crate::selection::{SelectEvent, Selected};
+
+    fn setup() -> App {
+        let mut app = App::new();
+        app
+            .add_state::<AppState>()
+            .add_state::<MatchState>()
+            .insert_resource(NextState(Some(AppState::InGame)))
+            .add_plugins((MinimalPlugins, InputPlugin, InputActionsPlugin, ControlGroupPlugin))
+            .insert_resource(InputBindings::default())
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(100)))
+            .add_event::<SelectEvent>()
+            .add_event::<CameraCommand>();
+        app.update();
+        app
+    }
+
+    fn tap(app: &mut App, key_code: KeyCode) {
+        for state in [ButtonState::Pressed, ButtonState::Released] {
+            app.world.send_event(KeyboardInput {
+                scan_code: 0,
+                key_code: Some(key_code),
+                state,
+                window: Entity::PLACEHOLDER,
+            });
+            app.update();
+        }
+    }
+
+    fn camera_commands(app: &App) -> Vec<CameraCommand> {
+        let events = app.world.resource::<Events<CameraCommand>>();
+        events.get_reader().iter(events).copied().collect()
+    }
+
+    #[test]
+    fn it_assigns_the_selection_while_assign_is_held() {
+        let mut app = setup();
+        let a = app.world.spawn((Transform::default(), Selected)).id();
+        app.world.spawn(Transform::default());
+        app.world.send_event(KeyboardInput {
+            scan_code: 0,
+            key_code: Some(KeyCode::ControlLeft),
+            state: ButtonState::Pressed,
+            window: Entity::PLACEHOLDER,
+        });
+        tap(&mut app, KeyCode::Key2);
+        assert_eq!(app.world.resource::<ControlGroups>().get(1), &[a]);
+    }
+
+    #[test]
+    fn it_centers_the_camera_when_a_group_is_double_tapped() {
+        let mut app = setup();
+        let a = app.world.spawn(Transform::from_xyz(2., 1., 0.)).id();
+        let b = app.world.spawn(Transform::from_xyz(4., 1., 4.)).id();
+        app.world.resource_mut::<ControlGroups>().assign(0, vec![a, b]);
+
+        tap(&mut app, KeyCode::Key1);
+        assert!(camera_commands(&app).is_empty());
+        tap(&mut app, KeyCode::Key1);
+        assert_eq!(camera_commands(&app), vec![CameraCommand::CenterOn(Vec3::new(3., 1., 2.))]);
+    }
+}
@@ -0,0 +1,350 @@
+use bevy::prelude::*;
+use bevy_mod_picking::prelude::*;
+use bevy_xpbd_3d::prelude::{Position, Rotation};
+use oxidized_navigation::debug_draw::DrawNavMesh;
+use crate::game_state::{pause_physics, resume_physics, AppState, MatchState};
+use crate::input::{ActionState, CursorPosition, InputAction, InputBindings};
+use crate::map::{spawn_gold_mine, spawn_obstacle, CurrentMap, GoldMineDef, MapDef, MapObject, MapSize, ObstacleDef, ObstacleShape, GOLD_MINE_SIZE};
+
+/// Degrees an obstacle turns per rotate action.
+const ROTATION_STEP: f32 = 15.;
+/// Factor an obstacle grows or shrinks by per scale action.
+const SCALE_STEP: f32 = 1.1;
+
+/// Map editor: move, rotate, scale, add and delete obstacles and gold mines, then save the map.
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<EditorSelection>()
+            .add_systems(Update, toggle_editor_system.run_if(in_state(AppState::InGame)))
+            .add_systems(OnEnter(MatchState::Editor), (setup_editor_ui, show_nav_mesh, pause_physics))
+            .add_systems(OnExit(MatchState::Editor), (cleanup_editor, hide_nav_mesh, resume_physics))
+            .add_systems(
+                Update,
+                (
+                    pick_object_system,
+                    drag_object_system,
+                    edit_object_system,
+                    add_object_system,
+                    save_map_system,
+                    draw_selection_system,
+                ).chain().run_if(in_state(MatchState::Editor)));
+    }
+}
+
+/// Map object being edited, and where the cursor grabbed it while dragging.
+#[derive(Resource, Default)]
+pub struct EditorSelection {
+    pub entity: Option<Entity>,
+    drag_offset: Option<Vec3>,
+}
+
+#[derive(Component)]
+struct EditorUi;
+
+/// `original` with its obstacles and gold mines replaced by the edited `objects`.
+pub fn edited_map<'a>(original: &MapDef, objects: impl Iterator<Item = (&'a MapObject, &'a Transform)>) -> MapDef {
+    let mut map = MapDef {
+        obstacles: Vec::new(),
+        gold_mines: Vec::new(),
+        ..original.clone()
+    };
+    for (object, transform) in objects {
+        match object.at(transform) {
+            MapObject::Obstacle(obstacle) => map.obstacles.push(obstacle),
+            MapObject::GoldMine(gold_mine) => map.gold_mines.push(gold_mine),
+        }
+    }
+    map
+}
+
+/// Width, height and depth of the box around `object`.
+fn object_size(object: &MapObject) -> Vec3 {
+    match object {
+        MapObject::Obstacle(ObstacleDef { shape: ObstacleShape::Box { size }, .. }) => *size,
+        MapObject::Obstacle(ObstacleDef { shape: ObstacleShape::Cylinder { radius, height }, .. }) =>
+            Vec3::new(radius * 2., *height, radius * 2.),
+        MapObject::GoldMine(_) => Vec3::splat(GOLD_MINE_SIZE),
+    }
+}
+
+fn toggle_editor_system(
+    actions: Res<ActionState>,
+    state: Res<State<MatchState>>,
+    mut next_state: ResMut<NextState<MatchState>>,
+) {
+    if !actions.just_pressed(InputAction::ToggleEditor) {
+        return;
+    }
+    match state.get() {
+        MatchState::Running => next_state.set(MatchState::Editor),
+        MatchState::Editor => next_state.set(MatchState::Running),
+        MatchState::Paused => {}
+    }
+}
+
+fn setup_editor_ui(mut commands: Commands) {
+    commands.spawn((
+        EditorUi,
+        TextBundle::from_section(
+            "EDITOR  drag: move  [ ]: rotate  - =: scale  Del: delete  O/G: add obstacle/mine  F5: save  F9: play",
+            TextStyle {
+                color: Color::ALICE_BLUE,
+                font_size: 14.,
+                ..default()
+            },
+        ).with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(8.),
+            left: Val::Px(8.),
+            ..default()
+        }),
+        Pickable::IGNORE,
+    ));
+}
+
+fn cleanup_editor(
+    mut commands: Commands,
+    mut selection: ResMut<EditorSelection>,
+    ui_q: Query<Entity, With<EditorUi>>,
+) {
+    *selection = EditorSelection::default();
+    for entity in ui_q.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+// The nav mesh is drawn while editing so its regeneration around moved obstacles is visible.
+fn show_nav_mesh(draw_nav_mesh: Option<ResMut<DrawNavMesh>>) {
+    if let Some(mut draw_nav_mesh) = draw_nav_mesh {
+        draw_nav_mesh.0 = true;
+    }
+}
+
+fn hide_nav_mesh(draw_nav_mesh: Option<ResMut<DrawNavMesh>>) {
+    if let Some(mut draw_nav_mesh) = draw_nav_mesh {
+        draw_nav_mesh.0 = false;
+    }
+}
+
+// Pressing select on a map object grabs it; pressing it anywhere else drops the selection.
+fn pick_object_system(
+    bindings: Res<InputBindings>,
+    cursor: Res<CursorPosition>,
+    mut presses: EventReader<Pointer<Down>>,
+    objects_q: Query<&Transform, With<MapObject>>,
+    mut selection: ResMut<EditorSelection>,
+) {
+    let select_buttons: Vec<PointerButton> = bindings.get(InputAction::Select)
+        .iter()
+        .filter_map(|binding| binding.pointer_button())
+        .collect();
+    let Some(press) = presses.iter().filter(|press| select_buttons.contains(&press.button)).last() else {
+        return;
+    };
+    match objects_q.get(press.target) {
+        Ok(transform) => {
+            selection.entity = Some(press.target);
+            selection.drag_offset = cursor.ground.map(|ground| transform.translation - ground);
+        }
+        Err(_) => *selection = EditorSelection::default(),
+    }
+}
+
+fn drag_object_system(
+    actions: Res<ActionState>,
+    cursor: Res<CursorPosition>,
+    mut selection: ResMut<EditorSelection>,
+    mut objects_q: Query<(&mut Transform, Option<&mut Position>), With<MapObject>>,
+) {
+    if !actions.pressed(InputAction::Select) {
+        selection.drag_offset = None;
+        return;
+    }
+    let (Some(entity), Some(offset), Some(ground)) = (selection.entity, selection.drag_offset, cursor.ground) else {
+        return;
+    };
+    let Ok((mut transform, position)) = objects_q.get_mut(entity) else {
+        return;
+    };
+    let translation = Vec3::new(ground.x + offset.x, transform.translation.y, ground.z + offset.z);
+    if transform.translation != translation {
+        transform.translation = translation;
+        if let Some(mut position) = position {
+            position.0 = translation;
+        }
+    }
+}
+
+// Rotating and scaling rebuild the obstacle's footprint and collider, which makes the nav mesh regenerate.
+fn edit_object_system(
+    mut commands: Commands,
+    actions: Res<ActionState>,
+    mut selection: ResMut<EditorSelection>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut objects_q: Query<(&mut Transform, &mut MapObject, Option<&mut Rotation>)>,
+) {
+    let Some(entity) = selection.entity else {
+        return;
+    };
+    if actions.just_pressed(InputAction::DeleteObject) {
+        commands.entity(entity).despawn_recursive();
+        *selection = EditorSelection::default();
+        return;
+    }
+    let Ok((mut transform, mut object, rotation)) = objects_q.get_mut(entity) else {
+        return;
+    };
+    let MapObject::Obstacle(obstacle) = object.at(&transform) else {
+        return;
+    };
+    let turn = match (actions.just_pressed(InputAction::RotateLeft), actions.just_pressed(InputAction::RotateRight)) {
+        (true, false) => ROTATION_STEP,
+        (false, true) => -ROTATION_STEP,
+        _ => 0.,
+    };
+    let scale = match (actions.just_pressed(InputAction::ScaleUp), actions.just_pressed(InputAction::ScaleDown)) {
+        (true, false) => SCALE_STEP,
+        (false, true) => 1. / SCALE_STEP,
+        _ => 1.,
+    };
+    if turn == 0. && scale == 1. {
+        return;
+    }
+
+    let edited = ObstacleDef {
+        rotation: obstacle.rotation + turn,
+        shape: obstacle.shape.scaled(scale),
+        ..obstacle
+    };
+    let yaw = edited.rotation.to_radians();
+    transform.translation = edited.position + Vec3::Y * edited.shape.height() / 2.;
+    transform.rotation = Quat::from_rotation_y(yaw);
+    if let Some(mut rotation) = rotation {
+        rotation.0 = transform.rotation;
+    }
+    *object = MapObject::Obstacle(edited);
+    let mut entity_commands = commands.entity(entity);
+    entity_commands.insert((edited.shape.collider(), edited.shape.footprint(yaw), Position(transform.translation)));
+    if scale != 1. {
+        entity_commands.insert(meshes.add(edited.shape.mesh()));
+    }
+}
+
+fn add_object_system(
+    mut commands: Commands,
+    actions: Res<ActionState>,
+    cursor: Res<CursorPosition>,
+    mut selection: ResMut<EditorSelection>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let Some(ground) = cursor.ground else {
+        return;
+    };
+    let position = Vec3::new(ground.x, 0., ground.z);
+    let added = if actions.just_pressed(InputAction::AddObstacle) {
+        spawn_obstacle(&mut commands, &mut meshes, &mut materials, ObstacleDef {
+            position,
+            rotation: 0.,
+            shape: ObstacleShape::Box { size: Vec3::new(4., 1.5, 1.) },
+            color: Color::GRAY,
+        })
+    } else if actions.just_pressed(InputAction::AddGoldMine) {
+        spawn_gold_mine(&mut commands, &mut meshes, &mut materials, GoldMineDef { position, reserves: 500 })
+    } else {
+        return;
+    };
+    *selection = EditorSelection { entity: Some(added), drag_offset: None };
+}
+
+fn save_map_system(
+    actions: Res<ActionState>,
+    current_map: Option<Res<CurrentMap>>,
+    map_size: Option<Res<MapSize>>,
+    maps: Res<Assets<MapDef>>,
+    objects_q: Query<(&MapObject, &Transform)>,
+) {
+    if !actions.just_pressed(InputAction::SaveMap) {
+        return;
+    }
+    let Some(current_map) = current_map else {
+        return;
+    };
+    let Some(original) = maps.get(&current_map.handle) else {
+        warn!("Can't save {}, it isn't loaded", current_map.path);
+        return;
+    };
+    let mut map = edited_map(original, objects_q.iter());
+    if let Some(map_size) = map_size {
+        map.size = map_size.0;
+    }
+    let path = format!("assets/{}", current_map.path);
+    match map.to_ron().map_err(|error| error.to_string())
+        .and_then(|ron| std::fs::write(&path, ron).map_err(|error| error.to_string())) {
+        Ok(()) => info!("Saved map {} to {}", map.name, path),
+        Err(error) => error!("Couldn't save map to {}: {}", path, error),
+    }
+}
+
+fn draw_selection_system(
+    mut gizmos: Gizmos,
+    selection: Res<EditorSelection>,
+    objects_q: Query<(&Transform, &MapObject)>,
+) {
+    let Some((transform, object)) = selection.entity.and_then(|entity| objects_q.get(entity).ok()) else {
+        return;
+    };
+    let size = object_size(object);
+    gizmos.cuboid(transform.with_scale(size * 1.05), Color::YELLOW);
+    gizmos.circle(
+        Vec3::new(transform.translation.x, 0.05, transform.translation.z),
+        Vec3::Y,
+        size.x.max(size.z) * 0.75,
+        Color::YELLOW,
+    );
+}
+
+#[cfg(test)]
+mod editor_test {
+    use bevy::prelude::*;
+    use crate::editor::edited_map;
+    use crate::map::{GoldMineDef, MapDef, MapObject, ObstacleDef, ObstacleShape};
+
+    fn wall() -> MapObject {
+        MapObject::Obstacle(ObstacleDef {
+            position: Vec3::ZERO,
+            rotation: 0.,
+            shape: ObstacleShape::Box { size: Vec3::new(4., 2., 1.) },
+            color: Color::GRAY,
+        })
+    }
+
+    #[test]
+    fn it_saves_moved_and_rotated_obstacles() {
+        let original = MapDef::from_ron(include_str!("../assets/maps/default.map.ron")).unwrap();
+        let transform = Transform::from_xyz(3., 1., -2.).with_rotation(Quat::from_rotation_y(90f32.to_radians()));
+        let map = edited_map(&original, [(&wall(), &transform)].into_iter());
+        assert_eq!(map.obstacles.len(), 1);
+        assert!(map.gold_mines.is_empty());
+        assert!(map.obstacles[0].position.abs_diff_eq(Vec3::new(3., 0., -2.), 1e-5));
+        assert!((map.obstacles[0].rotation - 90.).abs() < 1e-3);
+        assert_eq!(map.players.len(), original.players.len());
+    }
+
+    #[test]
+    fn it_round_trips_the_edited_map_through_ron() {
+        let original = MapDef::from_ron(include_str!("../assets/maps/default.map.ron")).unwrap();
+        let mine = MapObject::GoldMine(GoldMineDef { position: Vec3::ZERO, reserves: 300 });
+        let mine_transform = Transform::from_xyz(10., 1., 10.);
+        let wall_transform = Transform::from_xyz(-4., 1., 0.);
+        let map = edited_map(&original, [(&wall(), &wall_transform), (&mine, &mine_transform)].into_iter());
+
+        let reloaded = MapDef::from_ron(&map.to_ron().unwrap()).unwrap();
+        assert_eq!(reloaded.obstacles, map.obstacles);
+        assert_eq!(reloaded.gold_mines, vec![GoldMineDef { position: Vec3::new(10., 0., 10.), reserves: 300 }]);
+        assert_eq!(reloaded.size, original.size);
+    }
+}
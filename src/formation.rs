@@ -0,0 +1,204 @@
+use std::f32::consts::TAU;
+use bevy::prelude::*;
+use crate::input::{ActionState, InputAction};
+
+/// Shape the selected units arrange themselves in around a move target.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+pub enum Formation {
+    Line,
+    #[default]
+    Box,
+    Wedge,
+    Circle,
+}
+
+impl Formation {
+    pub fn next(self) -> Self {
+        match self {
+            Formation::Line => Formation::Box,
+            Formation::Box => Formation::Wedge,
+            Formation::Wedge => Formation::Circle,
+            Formation::Circle => Formation::Line,
+        }
+    }
+
+    /// Slot offsets for `count` units in formation space, where `x` is to the right of the
+    /// travel direction and `y` is along it.
+    pub fn offsets(&self, count: usize, spacing: f32) -> Vec<Vec2> {
+        match self {
+            Formation::Line => (0..count)
+                .map(|i| Vec2::new(centered(i, count) * spacing, 0.))
+                .collect(),
+            Formation::Box => {
+                let columns = (count as f32).sqrt().ceil().max(1.) as usize;
+                let rows = (count + columns - 1) / columns;
+                (0..count)
+                    .map(|i| {
+                        let row = i / columns;
+                        let in_row = if row == rows - 1 { count - row * columns } else { columns };
+                        Vec2::new(
+                            centered(i % columns, in_row) * spacing,
+                            -centered(row, rows) * spacing,
+                        )
+                    })
+                    .collect()
+            }
+            Formation::Wedge => (0..count)
+                .map(|i| {
+                    let rank = ((i + 1) / 2) as f32;
+                    let side = if i % 2 == 1 { -1. } else { 1. };
+                    Vec2::new(side * rank * spacing, -rank * spacing)
+                })
+                .collect(),
+            Formation::Circle => {
+                if count <= 1 {
+                    return vec![Vec2::ZERO; count];
+                }
+                let radius = (spacing * count as f32 / TAU).max(spacing);
+                (0..count)
+                    .map(|i| {
+                        let angle = TAU * i as f32 / count as f32;
+                        Vec2::new(angle.sin(), angle.cos()) * radius
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// World positions of the slots around `center`, oriented so the formation faces `direction`.
+    pub fn slots(&self, center: Vec3, direction: Vec3, count: usize, spacing: f32) -> Vec<Vec3> {
+        let forward = Vec3::new(direction.x, 0., direction.z).try_normalize().unwrap_or(Vec3::Z);
+        let right = Vec3::Y.cross(forward);
+        self.offsets(count, spacing)
+            .into_iter()
+            .map(|offset| center + right * offset.x + forward * offset.y)
+            .collect()
+    }
+}
+
+/// Offset of the `index`th of `count` evenly spaced items centered around zero.
+fn centered(index: usize, count: usize) -> f32 {
+    index as f32 - (count as f32 - 1.) / 2.
+}
+
+#[derive(Resource, Reflect)]
+pub struct FormationSettings {
+    pub formation: Formation,
+    pub spacing: f32,
+}
+
+impl Default for FormationSettings {
+    fn default() -> Self {
+        Self {
+            formation: Formation::default(),
+            spacing: 2.,
+        }
+    }
+}
+
+impl FormationSettings {
+    /// Gives each unit its own goal around `target`, facing from the group's center towards it.
+    pub fn plan(&self, target: Vec3, units: &[(Entity, Vec3)]) -> Vec<(Entity, Vec3)> {
+        if units.is_empty() {
+            return vec![];
+        }
+        let centroid = units.iter().map(|(_, position)| *position).sum::<Vec3>() / units.len() as f32;
+        let slots = self.formation.slots(target, target - centroid, units.len(), self.spacing);
+        assign_slots(units, &slots)
+    }
+}
+
+/// Greedily pairs units with slots, shortest distance first, so units don't cross the whole group.
+pub fn assign_slots(units: &[(Entity, Vec3)], slots: &[Vec3]) -> Vec<(Entity, Vec3)> {
+    let mut pairs: Vec<(f32, usize, usize)> = units.iter()
+        .enumerate()
+        .flat_map(|(unit, (_, position))| slots.iter()
+            .enumerate()
+            .map(move |(slot, goal)| (position.distance_squared(*goal), unit, slot)))
+        .collect();
+    pairs.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)).then(a.2.cmp(&b.2)));
+
+    let mut unit_taken = vec![false; units.len()];
+    let mut slot_taken = vec![false; slots.len()];
+    let mut assignments = Vec::with_capacity(units.len().min(slots.len()));
+    for (_, unit, slot) in pairs {
+        if unit_taken[unit] || slot_taken[slot] {
+            continue;
+        }
+        unit_taken[unit] = true;
+        slot_taken[slot] = true;
+        assignments.push((units[unit].0, slots[slot]));
+    }
+    assignments
+}
+
+pub fn cycle_formation_system(actions: Res<ActionState>, mut settings: ResMut<FormationSettings>) {
+    if actions.just_pressed(InputAction::CycleFormation) {
+        settings.formation = settings.formation.next();
+        info!("Formation: {:?}", settings.formation);
+    }
+}
+
+#[cfg(test)]
+mod formation_test {
+    use bevy::prelude::*;
+    use crate::formation::{assign_slots, Formation, FormationSettings};
+
+    const FORMATIONS: [Formation; 4] = [Formation::Line, Formation::Box, Formation::Wedge, Formation::Circle];
+
+    #[test]
+    fn it_gives_every_unit_a_distinct_slot() {
+        for formation in FORMATIONS {
+            let slots = formation.slots(Vec3::ZERO, Vec3::Z, 7, 2.);
+            assert_eq!(slots.len(), 7);
+            for (i, a) in slots.iter().enumerate() {
+                for b in slots.iter().skip(i + 1) {
+                    assert!(a.distance(*b) > 1., "{:?} has overlapping slots", formation);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn it_puts_a_single_unit_on_the_target() {
+        for formation in FORMATIONS {
+            let slots = formation.slots(Vec3::new(3., 0., 4.), Vec3::Z, 1, 2.);
+            assert_eq!(slots, vec![Vec3::new(3., 0., 4.)]);
+        }
+    }
+
+    #[test]
+    fn it_orients_line_perpendicular_to_travel_direction() {
+        let slots = Formation::Line.slots(Vec3::ZERO, Vec3::X, 3, 2.);
+        for slot in slots {
+            assert!(slot.x.abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn it_puts_the_wedge_tip_in_front() {
+        let slots = Formation::Wedge.slots(Vec3::ZERO, Vec3::Z, 5, 2.);
+        assert!(slots.iter().skip(1).all(|slot| slot.z < slots[0].z));
+    }
+
+    #[test]
+    fn it_assigns_nearest_slots() {
+        let a = Entity::from_raw(0);
+        let b = Entity::from_raw(1);
+        let units = [(a, Vec3::new(-5., 0., 0.)), (b, Vec3::new(5., 0., 0.))];
+        let slots = [Vec3::new(4., 0., 0.), Vec3::new(-4., 0., 0.)];
+        let assignments = assign_slots(&units, &slots);
+        assert!(assignments.contains(&(a, slots[1])));
+        assert!(assignments.contains(&(b, slots[0])));
+    }
+
+    #[test]
+    fn it_plans_one_goal_per_unit() {
+        let settings = FormationSettings::default();
+        let units: Vec<(Entity, Vec3)> = (0..4)
+            .map(|i| (Entity::from_raw(i), Vec3::new(i as f32, 0., 0.)))
+            .collect();
+        let plan = settings.plan(Vec3::new(0., 0., 20.), &units);
+        assert_eq!(plan.len(), 4);
+    }
+}
@@ -0,0 +1,139 @@
+use bevy::prelude::*;
+use crate::game_state::{AppState, MatchState};
+use crate::input::{ActionState, InputAction};
+
+/// Speeds the game can run at, relative to real time.
+pub const GAME_SPEEDS: [f32; 4] = [0.5, 1., 2., 4.];
+
+/// Pausing and speeding up the match by scaling Bevy's `Time`.
+///
+/// Physics steps, movement and the harvest, production and stuck timers all advance by `Time`'s delta,
+/// so they speed up, slow down and freeze together. Anything that should stay responsive while
+/// paused, such as the camera, reads the raw delta instead.
+pub struct GameSpeedPlugin;
+
+impl Plugin for GameSpeedPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<GameSpeed>()
+            .add_systems(
+                Update,
+                game_speed_input_system
+                    .run_if(in_state(AppState::InGame))
+                    .run_if(not(in_state(MatchState::Editor))))
+            .add_systems(Update, apply_game_speed)
+            .add_systems(OnEnter(AppState::Loading), reset_game_speed)
+            .add_systems(OnEnter(MatchState::Paused), pause_time)
+            .add_systems(OnExit(MatchState::Paused), unpause_time);
+    }
+}
+
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct GameSpeed {
+    index: usize,
+}
+
+impl Default for GameSpeed {
+    fn default() -> Self {
+        Self { index: 1 }
+    }
+}
+
+impl GameSpeed {
+    pub fn get(&self) -> f32 {
+        GAME_SPEEDS[self.index]
+    }
+
+    pub fn faster(&mut self) {
+        self.index = (self.index + 1).min(GAME_SPEEDS.len() - 1);
+    }
+
+    pub fn slower(&mut self) {
+        self.index = self.index.saturating_sub(1);
+    }
+}
+
+/// Run condition for systems that should stand still while the game is paused.
+pub fn time_running(time: Res<Time>) -> bool {
+    !time.is_paused()
+}
+
+fn game_speed_input_system(actions: Res<ActionState>, mut speed: ResMut<GameSpeed>) {
+    if actions.just_pressed(InputAction::SpeedUp) {
+        speed.faster();
+    }
+    if actions.just_pressed(InputAction::SlowDown) {
+        speed.slower();
+    }
+}
+
+fn apply_game_speed(speed: Res<GameSpeed>, mut time: ResMut<Time>) {
+    if speed.is_changed() {
+        info!("Game speed {}x", speed.get());
+        time.set_relative_speed(speed.get());
+    }
+}
+
+fn reset_game_speed(mut speed: ResMut<GameSpeed>) {
+    *speed = GameSpeed::default();
+}
+
+fn pause_time(mut time: ResMut<Time>) {
+    time.pause();
+}
+
+fn unpause_time(mut time: ResMut<Time>) {
+    time.unpause();
+}
+
+#[cfg(test)]
+mod game_speed_test {
+    use bevy::prelude::*;
+    use crate::game_speed::{GameSpeed, GameSpeedPlugin};
+    use crate::game_state::{AppState, MatchState};
+    use crate::input::ActionState;
+
+    fn setup() -> App {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, GameSpeedPlugin))
+            .add_state::<AppState>()
+            .add_state::<MatchState>()
+            .insert_resource(NextState(Some(AppState::InGame)))
+            .init_resource::<ActionState>();
+        app.update();
+        app
+    }
+
+    #[test]
+    fn it_clamps_game_speed() {
+        let mut speed = GameSpeed::default();
+        speed.slower();
+        speed.slower();
+        assert_eq!(speed.get(), 0.5);
+        for _ in 0..5 {
+            speed.faster();
+        }
+        assert_eq!(speed.get(), 4.);
+    }
+
+    #[test]
+    fn it_scales_time_by_game_speed() {
+        let mut app = setup();
+        app.world.resource_mut::<GameSpeed>().faster();
+        app.update();
+        assert_eq!(app.world.resource::<Time>().relative_speed(), 2.);
+    }
+
+    #[test]
+    fn it_pauses_time_while_paused() {
+        let mut app = setup();
+        app.world.resource_mut::<NextState<MatchState>>().set(MatchState::Paused);
+        app.update();
+        assert!(app.world.resource::<Time>().is_paused());
+
+        app.world.resource_mut::<NextState<MatchState>>().set(MatchState::Running);
+        app.update();
+        assert!(!app.world.resource::<Time>().is_paused());
+    }
+}
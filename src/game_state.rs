@@ -1,7 +1,288 @@
-use bevy::prelude::States;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy_xpbd_3d::prelude::PhysicsLoop;
+use oxidized_navigation::NavMesh;
+use crate::building::Building;
+use crate::harvest::GoldMine;
+use crate::input::{ActionState, InputAction};
+use crate::map::CurrentMap;
+use crate::player::{LocalPlayer, Owner};
+use crate::unit::Unit;
 
-#[derive(States, PartialEq, Eq, Debug, Clone, Hash, Default)]
+/// Frames the nav mesh's tiles have to stay the same before it counts as generated.
+const NAV_MESH_SETTLE_FRAMES: u32 = 10;
+
+#[derive(States, PartialEq, Eq, Debug, Clone, Copy, Hash, Default)]
 pub enum AppState {
     #[default]
+    MainMenu,
+    /// Waiting for the map, its unit types and the nav mesh before the match starts.
+    Loading,
+    /// A match is on, see `MatchState` for whether it's running.
     InGame,
+    /// The match is over, see `GameOutcome`.
+    GameOver,
+}
+
+/// What the match is doing while `AppState::InGame`. Pausing or editing doesn't leave `InGame`,
+/// so its `OnEnter` and `OnExit` only run when a match starts and ends.
+#[derive(States, PartialEq, Eq, Debug, Clone, Copy, Hash, Default)]
+pub enum MatchState {
+    #[default]
+    Running,
+    Paused,
+    /// Editing the map's obstacles and resources while gameplay and physics are paused.
+    Editor,
+}
+
+/// Moves between states as the match loads, pauses and ends, and cleans up after it.
+pub struct GameStatePlugin;
+
+impl Plugin for GameStatePlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<NavMeshLoading>()
+            .add_systems(OnEnter(AppState::Loading), start_nav_mesh_loading)
+            .add_systems(Update, finish_loading_system.run_if(in_state(AppState::Loading)))
+            .add_systems(Update, check_game_over_system.run_if(match_running))
+            .add_systems(Update, pause_input_system.run_if(in_state(AppState::InGame)))
+            .add_systems(OnExit(AppState::InGame), reset_match_state)
+            .add_systems(OnExit(AppState::GameOver), despawn_with::<MatchEntity>);
+    }
+}
+
+/// Anything spawned for a match, despawned once it's over so the next one starts clean.
+#[derive(Component, Default)]
+pub struct MatchEntity;
+
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameOutcome {
+    /// Every gold mine on the map was mined out.
+    Victory,
+    /// The player lost all of their units and buildings.
+    Defeat,
+}
+
+/// Nav mesh tile generations seen while loading, to tell when the tiles for the new map are done.
+#[derive(Resource, Default)]
+pub struct NavMeshLoading {
+    /// Generations when loading started, which may be left over from the previous map.
+    stale: HashMap<UVec2, u64>,
+    last: HashMap<UVec2, u64>,
+    unchanged_frames: u32,
+}
+
+impl NavMeshLoading {
+    pub fn new(stale: HashMap<UVec2, u64>) -> Self {
+        Self {
+            stale,
+            ..default()
+        }
+    }
+
+    /// Records this frame's generations, returning whether tiles were regenerated since loading
+    /// started and then stayed the same for `NAV_MESH_SETTLE_FRAMES`.
+    pub fn settled(&mut self, generations: &HashMap<UVec2, u64>) -> bool {
+        if *generations == self.last {
+            self.unchanged_frames += 1;
+        } else {
+            self.last = generations.clone();
+            self.unchanged_frames = 0;
+        }
+        *generations != self.stale && self.unchanged_frames >= NAV_MESH_SETTLE_FRAMES
+    }
+}
+
+/// Run condition for gameplay systems, which stand still while the match is paused or edited.
+pub fn match_running(app_state: Res<State<AppState>>, match_state: Res<State<MatchState>>) -> bool {
+    *app_state.get() == AppState::InGame && *match_state.get() == MatchState::Running
+}
+
+/// Despawns every entity tagged with `T`, for cleaning up when leaving a state.
+pub fn despawn_with<T: Component>(mut commands: Commands, tagged_q: Query<Entity, With<T>>) {
+    for entity in tagged_q.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub fn pause_physics(physics_loop: Option<ResMut<PhysicsLoop>>) {
+    if let Some(mut physics_loop) = physics_loop {
+        physics_loop.pause();
+    }
+}
+
+pub fn resume_physics(physics_loop: Option<ResMut<PhysicsLoop>>) {
+    if let Some(mut physics_loop) = physics_loop {
+        physics_loop.resume();
+    }
+}
+
+/// Outcome of a match from what's left on the field, or `None` while it's still going.
+pub fn game_outcome(units: usize, buildings: usize, mines: usize, depleted_mines: usize) -> Option<GameOutcome> {
+    if units == 0 && buildings == 0 {
+        Some(GameOutcome::Defeat)
+    } else if mines > 0 && depleted_mines == mines {
+        Some(GameOutcome::Victory)
+    } else {
+        None
+    }
+}
+
+fn start_nav_mesh_loading(mut commands: Commands, nav_mesh: Option<Res<NavMesh>>) {
+    let stale = nav_mesh
+        .and_then(|nav_mesh| nav_mesh.get().read().ok().map(|tiles| tiles.tile_generations.clone()))
+        .unwrap_or_default();
+    commands.insert_resource(NavMeshLoading::new(stale));
+}
+
+// The match starts once the map is on the field and the nav mesh has finished generating around it.
+fn finish_loading_system(
+    current_map: Option<Res<CurrentMap>>,
+    nav_mesh: Option<Res<NavMesh>>,
+    mut loading: ResMut<NavMeshLoading>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    if !current_map.is_some_and(|current_map| current_map.is_spawned()) {
+        return;
+    }
+    let nav_mesh_ready = match nav_mesh {
+        Some(nav_mesh) => {
+            let nav_mesh_lock = nav_mesh.get();
+            let Ok(tiles) = nav_mesh_lock.try_read() else {
+                return;
+            };
+            loading.settled(&tiles.tile_generations)
+        }
+        None => true,
+    };
+    if nav_mesh_ready {
+        info!("Map loaded, starting the match");
+        next_state.set(AppState::InGame);
+    }
+}
+
+// Only the local player's own units and buildings keep them in the match.
+fn check_game_over_system(
+    mut commands: Commands,
+    local: Res<LocalPlayer>,
+    units_q: Query<&Owner, With<Unit>>,
+    buildings_q: Query<&Owner, With<Building>>,
+    mines_q: Query<&GoldMine>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    let units = units_q.iter().filter(|owner| local.owns(owner)).count();
+    let buildings = buildings_q.iter().filter(|owner| local.owns(owner)).count();
+    let depleted_mines = mines_q.iter().filter(|mine| mine.is_depleted()).count();
+    if let Some(outcome) = game_outcome(units, buildings, mines_q.iter().len(), depleted_mines) {
+        info!("Game over: {:?}", outcome);
+        commands.insert_resource(outcome);
+        next_state.set(AppState::GameOver);
+    }
+}
+
+fn pause_input_system(
+    actions: Res<ActionState>,
+    state: Res<State<MatchState>>,
+    mut next_state: ResMut<NextState<MatchState>>,
+) {
+    if !actions.just_pressed(InputAction::Pause) {
+        return;
+    }
+    match state.get() {
+        MatchState::Running => next_state.set(MatchState::Paused),
+        MatchState::Paused => next_state.set(MatchState::Running),
+        MatchState::Editor => {}
+    }
+}
+
+// A match that ends while paused, such as by surrendering, leaves the next one running.
+fn reset_match_state(mut next_state: ResMut<NextState<MatchState>>) {
+    next_state.set(MatchState::Running);
+}
+
+#[cfg(test)]
+mod game_state_test {
+    use bevy::prelude::*;
+    use bevy::utils::HashMap;
+    use crate::game_state::{game_outcome, AppState, GameOutcome, GameStatePlugin, MatchEntity, MatchState, NavMeshLoading};
+    use crate::input::{ActionState, InputAction};
+    use crate::player::{LocalPlayer, Owner, PlayerId};
+    use crate::unit::Unit;
+
+    #[test]
+    fn it_is_defeat_without_units_or_buildings() {
+        assert_eq!(game_outcome(0, 0, 1, 0), Some(GameOutcome::Defeat));
+        assert_eq!(game_outcome(0, 1, 1, 0), None);
+    }
+
+    #[test]
+    fn it_is_victory_once_every_mine_is_depleted() {
+        assert_eq!(game_outcome(3, 1, 2, 2), Some(GameOutcome::Victory));
+        assert_eq!(game_outcome(3, 1, 2, 1), None);
+        assert_eq!(game_outcome(3, 1, 0, 0), None);
+    }
+
+    #[test]
+    fn it_waits_for_new_nav_mesh_tiles_to_settle() {
+        let stale = HashMap::from_iter([(UVec2::ZERO, 3)]);
+        let mut loading = NavMeshLoading::new(stale.clone());
+        // The previous map's tiles don't count, however long they stay.
+        for _ in 0..20 {
+            assert!(!loading.settled(&stale));
+        }
+        let mut generations = HashMap::from_iter([(UVec2::ZERO, 4), (UVec2::X, 1)]);
+        assert!(!loading.settled(&generations));
+        generations.insert(UVec2::Y, 1);
+        let settled = (0..20).position(|_| loading.settled(&generations));
+        assert_eq!(settled, Some(10));
+    }
+
+    #[test]
+    fn it_despawns_match_entities_when_leaving_game_over() {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, GameStatePlugin))
+            .add_state::<AppState>()
+            .add_state::<MatchState>()
+            .insert_resource(NextState(Some(AppState::GameOver)))
+            .init_resource::<ActionState>();
+        app.update();
+        let unit = app.world.spawn(MatchEntity).id();
+        let camera = app.world.spawn_empty().id();
+
+        app.world.resource_mut::<NextState<AppState>>().set(AppState::MainMenu);
+        app.update();
+
+        assert!(app.world.get_entity(unit).is_none());
+        assert!(app.world.get_entity(camera).is_some());
+    }
+
+    #[test]
+    fn it_pauses_without_leaving_the_match() {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, GameStatePlugin))
+            .add_state::<AppState>()
+            .add_state::<MatchState>()
+            .insert_resource(NextState(Some(AppState::InGame)))
+            .init_resource::<ActionState>()
+            .init_resource::<LocalPlayer>();
+        app.update();
+        // Keeps the match from ending for lack of units.
+        app.world.spawn((Unit("Worker".to_string()), Owner(PlayerId(0))));
+
+        app.world.resource_mut::<ActionState>().trigger(InputAction::Pause);
+        app.update();
+        app.world.insert_resource(ActionState::default());
+        app.update();
+        assert_eq!(*app.world.resource::<State<MatchState>>().get(), MatchState::Paused);
+        assert_eq!(*app.world.resource::<State<AppState>>().get(), AppState::InGame);
+
+        app.world.resource_mut::<ActionState>().trigger(InputAction::Pause);
+        app.update();
+        app.world.insert_resource(ActionState::default());
+        app.update();
+        assert_eq!(*app.world.resource::<State<MatchState>>().get(), MatchState::Running);
+        assert_eq!(*app.world.resource::<State<AppState>>().get(), AppState::InGame);
+    }
 }
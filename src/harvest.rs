@@ -0,0 +1,374 @@
+use bevy::prelude::*;
+use bevy_xpbd_3d::prelude::LinearVelocity;
+use crate::game_state::match_running;
+use crate::player::{Owner, Player};
+use crate::resources::{ResourceKind, Wallet};
+use crate::input::{ActionState, InputAction};
+use crate::movement::MovementPath;
+use crate::orders::{issue_move_orders, MoveEvent, OrderQueue};
+use crate::pathfinding::{AsyncPathfindingTasks, PathfindingSet, PathRequest, PathResult};
+use crate::selection::Selected;
+
+/// How close to a mine or depot's center a worker has to be to harvest from or deposit into it.
+const INTERACTION_RANGE: f32 = 3.;
+
+pub struct HarvestPlugin;
+
+impl Plugin for HarvestPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .register_type::<GoldMine>()
+            .register_type::<Worker>()
+            .add_systems(
+                Update,
+                (
+                    worker_system.before(PathfindingSet::Request),
+                    gather_order_system
+                        .after(issue_move_orders)
+                        .in_set(PathfindingSet::Request),
+                    give_up_unreachable_system.after(PathfindingSet::Process),
+                ).run_if(match_running));
+    }
+}
+
+/// Gold deposit with finite reserves, harvested by workers.
+#[derive(Component, Reflect)]
+pub struct GoldMine {
+    reserves: u32,
+}
+
+impl GoldMine {
+    pub fn new(reserves: u32) -> Self {
+        Self { reserves }
+    }
+
+    pub fn reserves(&self) -> u32 {
+        self.reserves
+    }
+
+    pub fn is_depleted(&self) -> bool {
+        self.reserves == 0
+    }
+
+    /// Takes up to `amount` gold out of the mine, returning how much was taken.
+    pub fn take(&mut self, amount: u32) -> u32 {
+        let taken = amount.min(self.reserves);
+        self.reserves -= taken;
+        taken
+    }
+}
+
+/// Building workers bring harvested gold back to.
+#[derive(Component, Default)]
+pub struct Depot;
+
+#[derive(Clone, Debug, Default, PartialEq, Reflect)]
+pub enum WorkerState {
+    #[default]
+    Idle,
+    MovingToMine,
+    Harvesting(Timer),
+    ReturningToDepot,
+}
+
+#[derive(Component, Reflect)]
+pub struct Worker {
+    /// Gold carried back to the depot per trip.
+    pub capacity: u32,
+    /// Seconds spent at the mine per trip.
+    pub harvest_time: f32,
+    carried: u32,
+    mine: Option<Entity>,
+    state: WorkerState,
+}
+
+impl Default for Worker {
+    fn default() -> Self {
+        Self {
+            capacity: 5,
+            harvest_time: 2.,
+            carried: 0,
+            mine: None,
+            state: WorkerState::Idle,
+        }
+    }
+}
+
+impl Worker {
+    pub fn new(capacity: u32, harvest_time: f32) -> Self {
+        Self {
+            capacity,
+            harvest_time,
+            ..default()
+        }
+    }
+
+    pub fn carried(&self) -> u32 {
+        self.carried
+    }
+
+    pub fn state(&self) -> &WorkerState {
+        &self.state
+    }
+
+    pub fn mine(&self) -> Option<Entity> {
+        self.mine
+    }
+
+    /// Starts the harvest loop on `mine`.
+    pub fn gather(&mut self, mine: Entity) {
+        self.mine = Some(mine);
+        self.state = WorkerState::MovingToMine;
+    }
+
+    pub fn stop(&mut self) {
+        self.mine = None;
+        self.state = WorkerState::Idle;
+    }
+}
+
+fn path_to(entity: Entity, goal: Vec3) -> PathRequest {
+    // Mines and depots are obstacles, so let the nav mesh query snap to the closest walkable point.
+    PathRequest {
+        entity,
+        goal,
+        search_radius: Some(INTERACTION_RANGE),
+    }
+}
+
+fn in_range(a: Vec3, b: Vec3) -> bool {
+    Vec2::new(a.x - b.x, a.z - b.z).length() <= INTERACTION_RANGE
+}
+
+// A move order given on a mine sends the selected workers gathering, any other order or stop ends the loop.
+fn gather_order_system(
+    mut move_events: EventReader<MoveEvent>,
+    actions: Res<ActionState>,
+    mines_q: Query<(Entity, &Transform, &GoldMine)>,
+    mut workers_q: Query<(Entity, &mut Worker, Option<&mut OrderQueue>), With<Selected>>,
+    mut path_requests: EventWriter<PathRequest>,
+) {
+    if actions.just_pressed(InputAction::Stop) || actions.just_pressed(InputAction::Hold) {
+        for (_, mut worker, _) in workers_q.iter_mut() {
+            worker.stop();
+        }
+    }
+    for event in move_events.iter() {
+        let mine = event.entity
+            .and_then(|entity| mines_q.get(entity).ok())
+            .filter(|(_, _, mine)| !mine.is_depleted())
+            .map(|(entity, transform, _)| (entity, transform.translation));
+        for (entity, mut worker, order_queue) in workers_q.iter_mut() {
+            match mine {
+                Some((mine, position)) => {
+                    worker.gather(mine);
+                    if let Some(mut order_queue) = order_queue {
+                        order_queue.clear();
+                    }
+                    path_requests.send(path_to(entity, position));
+                }
+                None if !event.queue => worker.stop(),
+                None => {}
+            }
+        }
+    }
+}
+
+fn worker_system(
+    time: Res<Time>,
+    pathfinding_tasks: Res<AsyncPathfindingTasks>,
+    mut mines_q: Query<(&Transform, &mut GoldMine)>,
+    depots_q: Query<(&Transform, &Owner), With<Depot>>,
+    mut wallet_q: Query<(&Player, &mut Wallet)>,
+    mut workers_q: Query<(Entity, &mut Worker, &Transform, &mut MovementPath, &mut LinearVelocity, &Owner)>,
+    mut path_requests: EventWriter<PathRequest>,
+) {
+    for (entity, mut worker, transform, mut path, mut velocity, owner) in workers_q.iter_mut() {
+        let position = transform.translation;
+        let walking = !path.is_empty() || pathfinding_tasks.is_pending(entity);
+        let mine = worker.mine.and_then(|mine| mines_q.get_mut(mine).ok());
+        // Workers only bring their harvest back to their owner's depots.
+        let nearest_depot = depots_q.iter()
+            .filter(|(_, depot_owner)| *depot_owner == owner)
+            .map(|(depot, _)| depot.translation)
+            .min_by(|a, b| a.distance_squared(position).total_cmp(&b.distance_squared(position)));
+
+        match worker.state.clone() {
+            WorkerState::Idle => {}
+            WorkerState::MovingToMine => {
+                let Some((mine_transform, mine)) = mine.filter(|(_, mine)| !mine.is_depleted()) else {
+                    worker.stop();
+                    continue;
+                };
+                if in_range(position, mine_transform.translation) {
+                    *path = MovementPath::default();
+                    velocity.0 = Vec3::new(0., velocity.0.y, 0.);
+                    worker.state = WorkerState::Harvesting(Timer::from_seconds(worker.harvest_time, TimerMode::Once));
+                } else if !walking {
+                    path_requests.send(path_to(entity, mine_transform.translation));
+                }
+            }
+            WorkerState::Harvesting(mut timer) => {
+                let Some((_, mut mine)) = mine else {
+                    worker.stop();
+                    continue;
+                };
+                if !timer.tick(time.delta()).finished() {
+                    worker.state = WorkerState::Harvesting(timer);
+                    continue;
+                }
+                worker.carried = mine.take(worker.capacity);
+                if worker.carried == 0 {
+                    worker.stop();
+                    continue;
+                }
+                worker.state = WorkerState::ReturningToDepot;
+                if let Some(depot) = nearest_depot.filter(|depot| !in_range(position, *depot)) {
+                    path_requests.send(path_to(entity, depot));
+                }
+            }
+            WorkerState::ReturningToDepot => {
+                let Some(depot) = nearest_depot else {
+                    continue;
+                };
+                if !in_range(position, depot) {
+                    if !walking {
+                        path_requests.send(path_to(entity, depot));
+                    }
+                    continue;
+                }
+                *path = MovementPath::default();
+                velocity.0 = Vec3::new(0., velocity.0.y, 0.);
+                if let Some((_, mut wallet)) = wallet_q.iter_mut().find(|(player, _)| player.id == owner.0) {
+                    wallet.gain(ResourceKind::Gold, worker.carried);
+                }
+                worker.carried = 0;
+                match mine {
+                    Some((mine_transform, mine)) if !mine.is_depleted() => {
+                        worker.state = WorkerState::MovingToMine;
+                        if !in_range(position, mine_transform.translation) {
+                            path_requests.send(path_to(entity, mine_transform.translation));
+                        }
+                    }
+                    _ => worker.stop(),
+                }
+            }
+        }
+    }
+}
+
+// Workers that can't reach their mine or depot even after snapping to the nav mesh stop trying.
+fn give_up_unreachable_system(
+    mut path_results: EventReader<PathResult>,
+    mut workers_q: Query<&mut Worker>,
+) {
+    for result in path_results.iter() {
+        if result.is_found() {
+            continue;
+        }
+        if let Ok(mut worker) = workers_q.get_mut(result.entity) {
+            if worker.state != WorkerState::Idle {
+                warn!("Worker {:?} can't reach its destination, stopping", result.entity);
+                worker.stop();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod harvest_test {
+    use bevy::prelude::*;
+    use bevy_xpbd_3d::prelude::LinearVelocity;
+    use crate::game_state::{AppState, MatchState};
+    use crate::resources::{ResourceKind, Wallet};
+    use crate::harvest::{Depot, GoldMine, HarvestPlugin, Worker, WorkerState};
+    use crate::input::ActionState;
+    use crate::movement::MovementPath;
+    use crate::orders::MoveEvent;
+    use crate::pathfinding::{AsyncPathfindingTasks, PathRequest, PathResult};
+    use crate::player::{Owner, Player, PlayerId, Team};
+    use crate::selection::Selected;
+
+    #[test]
+    fn it_takes_gold_from_mine() {
+        let mut mine = GoldMine::new(7);
+        assert_eq!(mine.take(5), 5);
+        assert_eq!(mine.reserves(), 2);
+    }
+
+    #[test]
+    fn it_doesnt_take_more_than_the_mine_has() {
+        let mut mine = GoldMine::new(2);
+        assert_eq!(mine.take(5), 2);
+        assert!(mine.is_depleted());
+    }
+
+    fn setup() -> App {
+        let mut app = App::new();
+        app
+            .add_state::<AppState>()
+            .add_state::<MatchState>()
+            .insert_resource(NextState(Some(AppState::InGame)))
+            .add_plugins((MinimalPlugins, HarvestPlugin))
+            .add_event::<MoveEvent>()
+            .add_event::<PathRequest>()
+            .add_event::<PathResult>()
+            .init_resource::<ActionState>()
+            .insert_resource(AsyncPathfindingTasks::default());
+        app
+    }
+
+    #[test]
+    fn it_sends_workers_gathering_from_the_mine_the_order_was_given_on() {
+        let mut app = setup();
+        let mine = app.world.spawn((TransformBundle::from(Transform::from_xyz(20., 1., 0.)), GoldMine::new(100))).id();
+        let worker = app.world.spawn((
+            TransformBundle::default(),
+            Worker::default(),
+            MovementPath::default(),
+            LinearVelocity::default(),
+            Owner(PlayerId(0)),
+            Selected,
+        )).id();
+        // Clicking the top of the mine hits the ground plane well past its center.
+        app.world.send_event(MoveEvent { target: Vec3::new(22.5, 0., 0.), queue: false, entity: Some(mine) });
+        app.update();
+
+        assert_eq!(app.world.get::<Worker>(worker).unwrap().mine(), Some(mine));
+        let events = app.world.resource::<Events<PathRequest>>();
+        let requests: Vec<PathRequest> = events.get_reader().iter(events).copied().collect();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].goal, Vec3::new(20., 1., 0.));
+    }
+
+    #[test]
+    fn it_harvests_until_mine_is_depleted() {
+        let mut app = setup();
+        // Mine and depot are both within reach, so the worker never has to walk.
+        let mine = app.world.spawn((TransformBundle::from(Transform::from_xyz(1., 0., 0.)), GoldMine::new(12))).id();
+        app.world.spawn((TransformBundle::from(Transform::from_xyz(-1., 0., 0.)), Depot, Owner(PlayerId(0))));
+        let wallet = app.world.spawn((Player { id: PlayerId(0), team: Team(0) }, Wallet::default())).id();
+        let other_wallet = app.world.spawn((Player { id: PlayerId(1), team: Team(1) }, Wallet::default())).id();
+        let mut worker = Worker {
+            harvest_time: 0.,
+            ..default()
+        };
+        worker.gather(mine);
+        let worker = app.world.spawn((
+            TransformBundle::default(),
+            worker,
+            MovementPath::default(),
+            LinearVelocity::default(),
+            Owner(PlayerId(0)),
+        )).id();
+
+        for _ in 0..20 {
+            app.update();
+        }
+
+        assert_eq!(app.world.get::<Wallet>(wallet).unwrap().balance(ResourceKind::Gold), 12);
+        assert_eq!(app.world.get::<Wallet>(other_wallet).unwrap().balance(ResourceKind::Gold), 0);
+        assert!(app.world.get::<GoldMine>(mine).unwrap().is_depleted());
+        assert_eq!(app.world.get::<Worker>(worker).unwrap().state(), &WorkerState::Idle);
+    }
+}
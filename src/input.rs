@@ -0,0 +1,340 @@
+use std::collections::HashMap;
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use bevy::ui::UiSystem;
+use bevy::utils::HashSet;
+use bevy::window::PrimaryWindow;
+use bevy_mod_picking::prelude::PointerButton;
+use serde::{Deserialize, Serialize};
+use crate::camera::MainCamera;
+
+pub const INPUT_BINDINGS_PATH: &str = "assets/input_bindings.ron";
+
+/// Maps raw mouse and keyboard input to gameplay actions, so systems don't hard-code buttons.
+pub struct InputActionsPlugin;
+
+impl Plugin for InputActionsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .insert_resource(InputBindings::load(INPUT_BINDINGS_PATH))
+            .init_resource::<ActionState>()
+            .init_resource::<CursorPosition>()
+            .add_systems(
+                PreUpdate,
+                (
+                    update_action_state.after(InputSystem),
+                    // Reads the `Interaction`s UI focus sets this frame.
+                    update_cursor_position.after(InputSystem).after(UiSystem::Focus),
+                ));
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum InputAction {
+    Select,
+    Move,
+    AttackMove,
+    Stop,
+    Hold,
+    /// Switches the selection's move formation to the next shape.
+    CycleFormation,
+    /// Modifier held to queue an order after the current ones.
+    Queue,
+    /// Modifier held to add to or toggle the selection instead of replacing it.
+    AddToSelection,
+    /// Queues the nth unit type the selected buildings can train.
+    Train(usize),
+    /// Cancels the last unit queued at the selected buildings.
+    CancelTraining,
+    /// Starts placing the nth building type.
+    PlaceBuilding(usize),
+    /// Backs out of the current mode, such as building placement.
+    Cancel,
+    /// Pans the camera towards the top of the screen.
+    PanForward,
+    /// Pans the camera towards the bottom of the screen.
+    PanBack,
+    /// Pans the camera towards the left of the screen.
+    PanLeft,
+    /// Pans the camera towards the right of the screen.
+    PanRight,
+    /// Turns the camera left around its focus point.
+    RotateCameraLeft,
+    /// Turns the camera right around its focus point.
+    RotateCameraRight,
+    /// Held to rotate the camera around its focus point by moving the mouse.
+    DragRotateCamera,
+    /// Keeps the camera on the selected unit, or stops following it.
+    FollowSelection,
+    /// Recalls the nth camera bookmark.
+    Bookmark(usize),
+    /// Selects the nth control group; twice in a row also centers the camera on it.
+    ControlGroup(usize),
+    /// Modifier held to assign a control group or save a camera bookmark instead of recalling it.
+    Assign,
+    /// Pauses or resumes the match.
+    Pause,
+    /// Runs the game at the next faster speed.
+    SpeedUp,
+    /// Runs the game at the next slower speed.
+    SlowDown,
+    /// Switches between playing and editing the map.
+    ToggleEditor,
+    /// Writes the edited map back to its file.
+    SaveMap,
+    RotateLeft,
+    RotateRight,
+    ScaleUp,
+    ScaleDown,
+    DeleteObject,
+    AddObstacle,
+    AddGoldMine,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Binding {
+    Mouse(MouseButton),
+    Key(KeyCode),
+}
+
+impl Binding {
+    /// Picking button matching this binding, if it is one of the pointer buttons.
+    pub fn pointer_button(&self) -> Option<PointerButton> {
+        match self {
+            Binding::Mouse(MouseButton::Left) => Some(PointerButton::Primary),
+            Binding::Mouse(MouseButton::Right) => Some(PointerButton::Secondary),
+            Binding::Mouse(MouseButton::Middle) => Some(PointerButton::Middle),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Resource, Clone, Debug, Serialize, Deserialize)]
+pub struct InputBindings {
+    bindings: HashMap<InputAction, Vec<Binding>>,
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        let shift = vec![Binding::Key(KeyCode::ShiftLeft), Binding::Key(KeyCode::ShiftRight)];
+        Self {
+            bindings: HashMap::from_iter([
+                (InputAction::Select, vec![Binding::Mouse(MouseButton::Left)]),
+                (InputAction::Move, vec![Binding::Mouse(MouseButton::Right)]),
+                (InputAction::AttackMove, vec![Binding::Key(KeyCode::T)]),
+                (InputAction::Stop, vec![Binding::Key(KeyCode::X)]),
+                (InputAction::Hold, vec![Binding::Key(KeyCode::H)]),
+                (InputAction::CycleFormation, vec![Binding::Key(KeyCode::V)]),
+                (InputAction::Queue, shift.clone()),
+                (InputAction::AddToSelection, shift),
+                (InputAction::Train(0), vec![Binding::Key(KeyCode::Y)]),
+                (InputAction::Train(1), vec![Binding::Key(KeyCode::U)]),
+                (InputAction::CancelTraining, vec![Binding::Key(KeyCode::Back)]),
+                (InputAction::PlaceBuilding(0), vec![Binding::Key(KeyCode::B)]),
+                (InputAction::PlaceBuilding(1), vec![Binding::Key(KeyCode::N)]),
+                (InputAction::Cancel, vec![Binding::Key(KeyCode::Escape)]),
+                (InputAction::PanForward, vec![Binding::Key(KeyCode::W), Binding::Key(KeyCode::Up)]),
+                (InputAction::PanBack, vec![Binding::Key(KeyCode::S), Binding::Key(KeyCode::Down)]),
+                (InputAction::PanLeft, vec![Binding::Key(KeyCode::A), Binding::Key(KeyCode::Left)]),
+                (InputAction::PanRight, vec![Binding::Key(KeyCode::D), Binding::Key(KeyCode::Right)]),
+                (InputAction::RotateCameraLeft, vec![Binding::Key(KeyCode::Q)]),
+                (InputAction::RotateCameraRight, vec![Binding::Key(KeyCode::E)]),
+                (InputAction::DragRotateCamera, vec![Binding::Mouse(MouseButton::Middle)]),
+                (InputAction::FollowSelection, vec![Binding::Key(KeyCode::F)]),
+                (InputAction::Bookmark(0), vec![Binding::Key(KeyCode::F1)]),
+                (InputAction::Bookmark(1), vec![Binding::Key(KeyCode::F2)]),
+                (InputAction::Bookmark(2), vec![Binding::Key(KeyCode::F3)]),
+                (InputAction::Bookmark(3), vec![Binding::Key(KeyCode::F4)]),
+                (InputAction::ControlGroup(0), vec![Binding::Key(KeyCode::Key1)]),
+                (InputAction::ControlGroup(1), vec![Binding::Key(KeyCode::Key2)]),
+                (InputAction::ControlGroup(2), vec![Binding::Key(KeyCode::Key3)]),
+                (InputAction::ControlGroup(3), vec![Binding::Key(KeyCode::Key4)]),
+                (InputAction::ControlGroup(4), vec![Binding::Key(KeyCode::Key5)]),
+                (InputAction::ControlGroup(5), vec![Binding::Key(KeyCode::Key6)]),
+                (InputAction::ControlGroup(6), vec![Binding::Key(KeyCode::Key7)]),
+                (InputAction::ControlGroup(7), vec![Binding::Key(KeyCode::Key8)]),
+                (InputAction::ControlGroup(8), vec![Binding::Key(KeyCode::Key9)]),
+                (InputAction::ControlGroup(9), vec![Binding::Key(KeyCode::Key0)]),
+                (InputAction::Assign, vec![Binding::Key(KeyCode::ControlLeft), Binding::Key(KeyCode::ControlRight)]),
+                (InputAction::Pause, vec![Binding::Key(KeyCode::P)]),
+                (InputAction::SpeedUp, vec![Binding::Key(KeyCode::Period)]),
+                (InputAction::SlowDown, vec![Binding::Key(KeyCode::Comma)]),
+                (InputAction::ToggleEditor, vec![Binding::Key(KeyCode::F9)]),
+                (InputAction::SaveMap, vec![Binding::Key(KeyCode::F5)]),
+                (InputAction::RotateLeft, vec![Binding::Key(KeyCode::BracketLeft)]),
+                (InputAction::RotateRight, vec![Binding::Key(KeyCode::BracketRight)]),
+                (InputAction::ScaleUp, vec![Binding::Key(KeyCode::Equals)]),
+                (InputAction::ScaleDown, vec![Binding::Key(KeyCode::Minus)]),
+                (InputAction::DeleteObject, vec![Binding::Key(KeyCode::Delete)]),
+                (InputAction::AddObstacle, vec![Binding::Key(KeyCode::O)]),
+                (InputAction::AddGoldMine, vec![Binding::Key(KeyCode::G)]),
+            ]),
+        }
+    }
+}
+
+impl InputBindings {
+    /// Reads bindings from a RON file, falling back to the defaults if it's missing or invalid.
+    pub fn load(path: &str) -> Self {
+        let bindings = std::fs::read_to_string(path)
+            .map_err(|error| error.to_string())
+            .and_then(|ron| Self::from_ron(&ron));
+        match bindings {
+            Ok(bindings) => bindings,
+            Err(error) => {
+                warn!("Using default input bindings, couldn't load {}: {}", path, error);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn from_ron(ron: &str) -> Result<Self, String> {
+        ron::from_str(ron).map_err(|error| error.to_string())
+    }
+
+    pub fn get(&self, action: InputAction) -> &[Binding] {
+        self.bindings.get(&action).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    #[cfg(test)]
+    pub fn bind(&mut self, action: InputAction, bindings: Vec<Binding>) {
+        self.bindings.insert(action, bindings);
+    }
+}
+
+/// Actions pressed this frame, derived from `InputBindings`.
+#[derive(Resource, Default)]
+pub struct ActionState {
+    pressed: HashSet<InputAction>,
+    just_pressed: HashSet<InputAction>,
+    just_released: HashSet<InputAction>,
+}
+
+impl ActionState {
+    pub fn pressed(&self, action: InputAction) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    pub fn just_pressed(&self, action: InputAction) -> bool {
+        self.just_pressed.contains(&action)
+    }
+
+    pub fn just_released(&self, action: InputAction) -> bool {
+        self.just_released.contains(&action)
+    }
+
+    /// Presses `action` for this frame only, for UI buttons standing in for its hotkey.
+    pub fn trigger(&mut self, action: InputAction) {
+        self.just_pressed.insert(action);
+    }
+}
+
+pub(crate) fn update_action_state(
+    bindings: Res<InputBindings>,
+    keys: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    mut actions: ResMut<ActionState>,
+) {
+    actions.just_pressed.clear();
+    actions.just_released.clear();
+    for (action, action_bindings) in bindings.bindings.iter() {
+        let pressed = action_bindings.iter().any(|binding| match binding {
+            Binding::Mouse(button) => mouse.pressed(*button),
+            Binding::Key(key) => keys.pressed(*key),
+        });
+        let was_pressed = actions.pressed.contains(action);
+        if pressed && !was_pressed {
+            actions.pressed.insert(*action);
+            actions.just_pressed.insert(*action);
+        } else if !pressed && was_pressed {
+            actions.pressed.remove(action);
+            actions.just_released.insert(*action);
+        }
+    }
+}
+
+/// Where the cursor is on screen and where it points at on the ground plane.
+#[derive(Resource, Default)]
+pub struct CursorPosition {
+    pub screen: Option<Vec2>,
+    /// `None` while the cursor is over an interactive UI panel, so clicks there don't reach the world.
+    pub ground: Option<Vec3>,
+    pub over_ui: bool,
+}
+
+fn update_cursor_position(
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    interactions_q: Query<&Interaction>,
+    mut cursor: ResMut<CursorPosition>,
+) {
+    cursor.screen = window_q.get_single().ok().and_then(Window::cursor_position);
+    cursor.over_ui = interactions_q.iter().any(|interaction| *interaction != Interaction::None);
+    cursor.ground = cursor.screen
+        .filter(|_| !cursor.over_ui)
+        .zip(camera_q.get_single().ok())
+        .and_then(|(screen, (camera, camera_transform))| camera.viewport_to_world(camera_transform, screen))
+        .and_then(|ray| ray.intersect_plane(Vec3::ZERO, Vec3::Y).map(|distance| ray.get_point(distance)));
+}
+
+#[cfg(test)]
+mod input_test {
+    use bevy::input::ButtonState;
+    use bevy::input::keyboard::KeyboardInput;
+    use bevy::input::InputPlugin;
+    use bevy::prelude::*;
+    use crate::input::{ActionState, Binding, InputAction, InputActionsPlugin, InputBindings};
+
+    fn setup() -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, InputPlugin, InputActionsPlugin));
+        app.insert_resource(InputBindings::default());
+        app.update();
+        app
+    }
+
+    fn send_key(app: &mut App, key_code: KeyCode, state: ButtonState) {
+        app.world.send_event(KeyboardInput {
+            scan_code: 0,
+            key_code: Some(key_code),
+            state,
+            window: Entity::PLACEHOLDER,
+        });
+        app.update();
+    }
+
+    #[test]
+    fn it_parses_the_bundled_bindings_file() {
+        let bindings = InputBindings::from_ron(include_str!("../assets/input_bindings.ron")).unwrap();
+        assert_eq!(bindings.get(InputAction::Move), &[Binding::Mouse(MouseButton::Right)]);
+    }
+
+    #[test]
+    fn it_presses_action_bound_to_key() {
+        let mut app = setup();
+        send_key(&mut app, KeyCode::X, ButtonState::Pressed);
+        let actions = app.world.resource::<ActionState>();
+        assert!(actions.just_pressed(InputAction::Stop));
+        assert!(actions.pressed(InputAction::Stop));
+    }
+
+    #[test]
+    fn it_releases_action_bound_to_key() {
+        let mut app = setup();
+        send_key(&mut app, KeyCode::X, ButtonState::Pressed);
+        send_key(&mut app, KeyCode::X, ButtonState::Released);
+        let actions = app.world.resource::<ActionState>();
+        assert!(actions.just_released(InputAction::Stop));
+        assert!(!actions.pressed(InputAction::Stop));
+    }
+
+    #[test]
+    fn it_uses_rebound_keys() {
+        let mut app = setup();
+        app.world.resource_mut::<InputBindings>().bind(InputAction::Stop, vec![Binding::Key(KeyCode::P)]);
+        send_key(&mut app, KeyCode::X, ButtonState::Pressed);
+        assert!(!app.world.resource::<ActionState>().pressed(InputAction::Stop));
+        send_key(&mut app, KeyCode::P, ButtonState::Pressed);
+        assert!(app.world.resource::<ActionState>().pressed(InputAction::Stop));
+    }
+}
@@ -1,17 +1,35 @@
+use std::time::Duration;
+use bevy::asset::ChangeWatcher;
 use bevy::prelude::*;
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
 use bevy_mod_picking::prelude::*;
 use bevy_xpbd_3d::prelude::PhysicsPlugins;
 use pathfinding::PathfindingPlugin;
 use crate::camera::MyCameraPlugin;
-use crate::game_state::AppState;
-use crate::gold_resource::ResourcesPlugin;
+use crate::game_state::{AppState, GameStatePlugin, MatchState};
+use crate::resources::ResourcesPlugin;
 use crate::ui::UIPlugin;
+use crate::ui::menu::MenuPlugin;
+use crate::ui::minimap::MinimapPlugin;
+use crate::ui::selection_panel::SelectionPanelPlugin;
 
 use world::setup_3d_scene;
 use crate::movement::MovementPlugin;
+use crate::selection::SelectionPlugin;
+use crate::orders::OrdersPlugin;
+use crate::input::InputActionsPlugin;
+use crate::harvest::HarvestPlugin;
+use crate::supply::SupplyPlugin;
+use crate::production::ProductionPlugin;
+use crate::placement::PlacementPlugin;
+use crate::unit::UnitPlugin;
+use crate::map::MapPlugin;
+use crate::editor::EditorPlugin;
+use crate::game_speed::GameSpeedPlugin;
+use crate::control_group::ControlGroupPlugin;
+use crate::player::PlayerPlugin;
 
-mod gold_resource;
+mod resources;
 mod ui;
 mod game_state;
 mod camera;
@@ -19,21 +37,60 @@ mod supply;
 mod pathfinding;
 mod world;
 mod movement;
+mod selection;
+mod formation;
+mod avoidance;
+mod orders;
+mod input;
+mod harvest;
+mod unit;
+mod production;
+mod building;
+mod placement;
+mod map;
+mod editor;
+mod game_speed;
+mod control_group;
+mod player;
 
 fn main() {
     let mut app = App::new();
     app.add_state::<AppState>();
+    app.add_state::<MatchState>();
     app.add_plugins((
-        DefaultPlugins,
+        DefaultPlugins.set(AssetPlugin {
+            // Reload edited unit types while the game is running.
+            watch_for_changes: ChangeWatcher::with_delay(Duration::from_millis(200)),
+            ..default()
+        }),
         PhysicsPlugins::default(),
         PathfindingPlugin::default(),
         DefaultPickingPlugins.build()
             .disable::<DefaultHighlightingPlugin>(),
         WorldInspectorPlugin::new(),
+        InputActionsPlugin,
         MyCameraPlugin,
         UIPlugin,
+        MenuPlugin,
+        MinimapPlugin,
+        SelectionPanelPlugin,
+        GameStatePlugin,
+        GameSpeedPlugin,
+    ));
+    app.add_plugins((
+        PlayerPlugin,
+        UnitPlugin,
+        MapPlugin,
         ResourcesPlugin,
+        SupplyPlugin,
         MovementPlugin,
+        SelectionPlugin,
+        ControlGroupPlugin,
+        OrdersPlugin,
+        HarvestPlugin,
+        ProductionPlugin,
+        PlacementPlugin,
+        EditorPlugin,
     ));
     app.add_systems(Startup, setup_3d_scene);
     app.run();
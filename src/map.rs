@@ -0,0 +1,405 @@
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::{TypePath, TypeUuid};
+use bevy::utils::BoxedFuture;
+use bevy_mod_picking::prelude::*;
+use bevy_xpbd_3d::prelude::{Collider, RigidBody};
+use oxidized_navigation::NavMeshAffector;
+use serde::{Deserialize, Serialize};
+use crate::building::{BuildingKind, Footprint};
+use crate::camera::CameraCommand;
+use crate::game_state::{AppState, MatchEntity};
+use crate::harvest::GoldMine;
+use crate::player::{LocalPlayer, Owner, Player, PlayerId, Team};
+use crate::unit::{find_unit_def, spawn_unit, UnitDef};
+use crate::world::{spawn_building, BuildingAssets};
+
+pub const DEFAULT_MAP_PATH: &str = "maps/default.map.ron";
+
+/// Size of a gold mine's cube.
+pub const GOLD_MINE_SIZE: f32 = 2.;
+
+pub struct MapPlugin;
+
+impl Plugin for MapPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_asset::<MapDef>()
+            .init_asset_loader::<MapDefLoader>()
+            .init_resource::<BuildingAssets>()
+            .register_type::<MapObject>()
+            .add_systems(OnEnter(AppState::Loading), load_map)
+            .add_systems(Update, spawn_map.run_if(in_state(AppState::Loading)));
+    }
+}
+
+/// A level: the ground, static obstacles, resources and where each player starts.
+#[derive(Debug, Clone, Serialize, Deserialize, TypeUuid, TypePath)]
+#[uuid = "0f9d8c3e-5b2a-4e7f-8a61-3c4d2b9e7a15"]
+pub struct MapDef {
+    pub name: String,
+    /// Width and depth of the ground plane, centered on the origin.
+    pub size: f32,
+    #[serde(default)]
+    pub obstacles: Vec<ObstacleDef>,
+    #[serde(default)]
+    pub gold_mines: Vec<GoldMineDef>,
+    pub players: Vec<PlayerStart>,
+}
+
+impl MapDef {
+    pub fn from_ron(ron: &str) -> Result<Self, ron::error::SpannedError> {
+        ron::from_str(ron)
+    }
+
+    pub fn to_ron(&self) -> Result<String, ron::Error> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Reflect)]
+pub enum ObstacleShape {
+    Box { size: Vec3 },
+    Cylinder { radius: f32, height: f32 },
+}
+
+impl ObstacleShape {
+    pub fn height(&self) -> f32 {
+        match self {
+            ObstacleShape::Box { size } => size.y,
+            ObstacleShape::Cylinder { height, .. } => *height,
+        }
+    }
+
+    pub fn collider(&self) -> Collider {
+        match self {
+            ObstacleShape::Box { size } => Collider::cuboid(size.x, size.y, size.z),
+            ObstacleShape::Cylinder { radius, height } => Collider::cylinder(*height, *radius),
+        }
+    }
+
+    pub fn mesh(&self) -> Mesh {
+        match self {
+            ObstacleShape::Box { size } => Mesh::from(shape::Box::new(size.x, size.y, size.z)),
+            ObstacleShape::Cylinder { radius, height } => Mesh::from(shape::Cylinder {
+                radius: *radius,
+                height: *height,
+                ..default()
+            }),
+        }
+    }
+
+    /// Ground area covered when turned `rotation` radians around Y.
+    pub fn footprint(&self, rotation: f32) -> Footprint {
+        match self {
+            ObstacleShape::Box { size } => {
+                let (sin, cos) = (rotation.sin().abs(), rotation.cos().abs());
+                Footprint::new((cos * size.x + sin * size.z) / 2., (sin * size.x + cos * size.z) / 2.)
+            }
+            ObstacleShape::Cylinder { radius, .. } => Footprint::new(*radius, *radius),
+        }
+    }
+
+    /// The same shape, `factor` times larger in every direction.
+    pub fn scaled(&self, factor: f32) -> Self {
+        match self {
+            ObstacleShape::Box { size } => ObstacleShape::Box { size: *size * factor },
+            ObstacleShape::Cylinder { radius, height } => ObstacleShape::Cylinder {
+                radius: radius * factor,
+                height: height * factor,
+            },
+        }
+    }
+}
+
+/// Static obstacle standing on the ground at `position`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Reflect)]
+pub struct ObstacleDef {
+    pub position: Vec3,
+    /// Degrees around the Y axis.
+    #[serde(default)]
+    pub rotation: f32,
+    pub shape: ObstacleShape,
+    pub color: Color,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Reflect)]
+pub struct GoldMineDef {
+    pub position: Vec3,
+    pub reserves: u32,
+}
+
+/// Buildings and units a player starts with, by type and ground position.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayerStart {
+    /// Players without a team each play on their own.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub team: Option<Team>,
+    /// Ground position of the player's base, where the camera starts.
+    pub start: Vec3,
+    #[serde(default)]
+    pub buildings: Vec<(BuildingKind, Vec3)>,
+    #[serde(default)]
+    pub units: Vec<(String, Vec3)>,
+}
+
+#[derive(Default)]
+pub struct MapDefLoader;
+
+impl AssetLoader for MapDefLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let map: MapDef = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(map));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["map.ron"]
+    }
+}
+
+/// The map being played, where it was loaded from, and whether it has been spawned yet.
+#[derive(Resource)]
+pub struct CurrentMap {
+    pub handle: Handle<MapDef>,
+    /// Path under `assets` the map is loaded from and saved to.
+    pub path: String,
+    spawned: bool,
+}
+
+impl CurrentMap {
+    pub fn new(path: &str, handle: Handle<MapDef>) -> Self {
+        Self { handle, path: path.to_string(), spawned: false }
+    }
+
+    pub fn is_spawned(&self) -> bool {
+        self.spawned
+    }
+}
+
+/// Width and depth of the spawned map's ground.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct MapSize(pub f32);
+
+/// Ground plane of the map.
+#[derive(Component)]
+pub struct Ground;
+
+/// Map definition an editable obstacle or resource node was spawned from.
+/// The entity's `Transform` is authoritative for its position and rotation.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Reflect)]
+pub enum MapObject {
+    Obstacle(ObstacleDef),
+    GoldMine(GoldMineDef),
+}
+
+impl MapObject {
+    /// Definition of the object as it currently stands at `transform`.
+    pub fn at(&self, transform: &Transform) -> Self {
+        match self {
+            MapObject::Obstacle(obstacle) => MapObject::Obstacle(ObstacleDef {
+                position: transform.translation - Vec3::Y * obstacle.shape.height() / 2.,
+                rotation: transform.rotation.to_euler(EulerRot::YXZ).0.to_degrees(),
+                ..*obstacle
+            }),
+            MapObject::GoldMine(gold_mine) => MapObject::GoldMine(GoldMineDef {
+                position: transform.translation - Vec3::Y * GOLD_MINE_SIZE / 2.,
+                ..*gold_mine
+            }),
+        }
+    }
+}
+
+fn load_map(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(CurrentMap::new(DEFAULT_MAP_PATH, asset_server.load(DEFAULT_MAP_PATH)));
+}
+
+pub fn spawn_obstacle(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    obstacle: ObstacleDef,
+) -> Entity {
+    let rotation = obstacle.rotation.to_radians();
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(obstacle.shape.mesh()),
+            material: materials.add(obstacle.color.into()),
+            transform: Transform::from_translation(obstacle.position + Vec3::Y * obstacle.shape.height() / 2.)
+                .with_rotation(Quat::from_rotation_y(rotation)),
+            ..default()
+        },
+        RigidBody::Static,
+        obstacle.shape.collider(),
+        NavMeshAffector,
+        obstacle.shape.footprint(rotation),
+        PickableBundle::default(),
+        MapObject::Obstacle(obstacle),
+        MatchEntity,
+    )).id()
+}
+
+pub fn spawn_gold_mine(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    gold_mine: GoldMineDef,
+) -> Entity {
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::Cube { size: GOLD_MINE_SIZE })),
+            material: materials.add(Color::rgb(0.9, 0.75, 0.1).into()),
+            transform: Transform::from_translation(gold_mine.position + Vec3::Y * GOLD_MINE_SIZE / 2.),
+            ..default()
+        },
+        RigidBody::Static,
+        Collider::cuboid(GOLD_MINE_SIZE, GOLD_MINE_SIZE, GOLD_MINE_SIZE),
+        NavMeshAffector,
+        Footprint::new(GOLD_MINE_SIZE / 2., GOLD_MINE_SIZE / 2.),
+        GoldMine::new(gold_mine.reserves),
+        PickableBundle::default(),
+        MapObject::GoldMine(gold_mine),
+        MatchEntity,
+    )).id()
+}
+
+// Maps refer to unit types by name, so spawning waits until the map and all of its unit types are loaded.
+fn spawn_map(
+    mut commands: Commands,
+    mut camera_commands: EventWriter<CameraCommand>,
+    local: Res<LocalPlayer>,
+    current_map: Option<ResMut<CurrentMap>>,
+    maps: Res<Assets<MapDef>>,
+    unit_defs: Res<Assets<UnitDef>>,
+    building_assets: Res<BuildingAssets>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let Some(mut current_map) = current_map.filter(|current_map| !current_map.spawned) else {
+        return;
+    };
+    let Some(map) = maps.get(&current_map.handle) else {
+        return;
+    };
+    let units: Option<Vec<(&UnitDef, Vec3, Owner)>> = map.players.iter()
+        .enumerate()
+        .flat_map(|(index, player)| player.units.iter().map(move |unit| (unit, Owner(PlayerId(index as u8)))))
+        .map(|((name, position), owner)| find_unit_def(&unit_defs, name).map(|(_, unit_def)| (unit_def, *position, owner)))
+        .collect();
+    let Some(units) = units else {
+        return;
+    };
+    current_map.spawned = true;
+    info!("Spawning map {}", map.name);
+
+    commands.insert_resource(MapSize(map.size));
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::Plane {
+                size: map.size,
+                subdivisions: 0,
+            })),
+            material: materials.add(Color::rgb(0.3, 0.5, 0.3).into()),
+            ..default()
+        },
+        RigidBody::Static,
+        Collider::cuboid(map.size, 0.5, map.size),
+        PickableBundle::default(),
+        NavMeshAffector,
+        Ground,
+        MatchEntity,
+    ));
+
+    for obstacle in map.obstacles.iter() {
+        spawn_obstacle(&mut commands, &mut meshes, &mut materials, *obstacle);
+    }
+    for gold_mine in map.gold_mines.iter() {
+        spawn_gold_mine(&mut commands, &mut meshes, &mut materials, *gold_mine);
+    }
+
+    for (index, player) in map.players.iter().enumerate() {
+        let id = PlayerId(index as u8);
+        commands.spawn((Player { id, team: player.team.unwrap_or(Team(id.0)) }, MatchEntity));
+        if id == local.0 {
+            camera_commands.send(CameraCommand::CenterOn(player.start));
+        }
+        for (kind, position) in player.buildings.iter() {
+            spawn_building(&mut commands, &building_assets, *kind, *position, Owner(id));
+        }
+    }
+    for (unit_def, position, owner) in units {
+        spawn_unit(&mut commands, unit_def, position, owner);
+    }
+}
+
+#[cfg(test)]
+mod map_test {
+    use bevy::prelude::*;
+    use oxidized_navigation::NavMeshAffector;
+    use crate::building::Building;
+    use crate::camera::CameraCommand;
+    use crate::game_state::AppState;
+    use crate::harvest::GoldMine;
+    use crate::map::{CurrentMap, MapDef, MapPlugin};
+    use crate::player::{LocalPlayer, Player};
+    use crate::unit::{Unit, UnitPlugin};
+
+    fn count<C: Component>(app: &mut App) -> usize {
+        app.world.query_filtered::<Entity, With<C>>().iter(&app.world).count()
+    }
+
+    #[test]
+    fn it_parses_the_bundled_map() {
+        let map = MapDef::from_ron(include_str!("../assets/maps/default.map.ron")).unwrap();
+        assert_eq!(map.size, 75.);
+        assert_eq!(map.players.len(), 1);
+        assert_eq!(map.players[0].start, Vec3::new(-15., 0., -10.));
+    }
+
+    #[test]
+    fn it_spawns_the_default_map_headlessly() {
+        let mut app = App::new();
+        app
+            .add_plugins((MinimalPlugins, AssetPlugin::default()))
+            .add_asset::<Mesh>()
+            .add_asset::<StandardMaterial>()
+            .add_plugins((UnitPlugin, MapPlugin))
+            .add_state::<AppState>()
+            .init_resource::<LocalPlayer>()
+            .add_event::<CameraCommand>()
+            .insert_resource(NextState(Some(AppState::Loading)));
+        for _ in 0..500 {
+            app.update();
+            if app.world.get_resource::<CurrentMap>().is_some_and(CurrentMap::is_spawned) {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        app.update();
+
+        let map = MapDef::from_ron(include_str!("../assets/maps/default.map.ron")).unwrap();
+        let buildings: usize = map.players.iter().map(|player| player.buildings.len()).sum();
+        let units: usize = map.players.iter().map(|player| player.units.len()).sum();
+        assert_eq!(count::<Building>(&mut app), buildings);
+        assert_eq!(count::<GoldMine>(&mut app), map.gold_mines.len());
+        assert_eq!(count::<Unit>(&mut app), units);
+        assert_eq!(count::<Player>(&mut app), map.players.len());
+        // The ground, every obstacle, mine and building all carve the nav mesh.
+        assert_eq!(count::<NavMeshAffector>(&mut app), 1 + map.obstacles.len() + map.gold_mines.len() + buildings);
+        // The camera starts over the local player's base.
+        let camera_commands = app.world.resource::<Events<CameraCommand>>();
+        let centered_on: Vec<Vec3> = camera_commands.get_reader().iter(camera_commands)
+            .filter_map(|command| match command {
+                CameraCommand::CenterOn(position) => Some(*position),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(centered_on, vec![map.players[0].start]);
+    }
+}
@@ -1,14 +1,28 @@
 use bevy::math::Vec3;
 use bevy::prelude::*;
-use bevy_xpbd_3d::prelude::{LinearVelocity, Position};
-use crate::game_state::AppState;
+use bevy_xpbd_3d::prelude::{LinearVelocity, Rotation};
+use serde::Deserialize;
+use crate::avoidance::{avoidance_system, Avoidance, AvoidanceSettings};
+use crate::game_state::match_running;
 
 pub struct MovementPlugin;
 
 impl Plugin for MovementPlugin {
     fn build(&self, app: &mut App) {
-        app.register_type::<MovementPath>();
-        app.add_systems(Update, movement_system.run_if(in_state(AppState::InGame)));
+        app.register_type::<MovementPath>()
+            .register_type::<MovementStats>()
+            .register_type::<Avoidance>()
+            .register_type::<AvoidanceSettings>()
+            .register_type::<StuckTracker>()
+            .register_type::<StuckSettings>()
+            .init_resource::<AvoidanceSettings>()
+            .init_resource::<StuckSettings>()
+            .add_event::<UnitStuck>();
+        app.add_systems(
+            Update,
+            (movement_system, stuck_detection_system, recover_stuck_units, avoidance_system)
+                .chain()
+                .run_if(match_running));
     }
 }
 
@@ -19,6 +33,15 @@ impl MovementPath {
     pub fn new(path: Vec<Vec3>) -> Self {
         Self(path)
     }
+    pub fn goal(&self) -> Option<Vec3> {
+        self.0.last().copied()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+    pub fn waypoints(&self) -> &[Vec3] {
+        &self.0
+    }
     pub fn remove_first(&mut self){
         if !self.0.is_empty() {
             self.0.remove(0);
@@ -26,22 +49,330 @@ impl MovementPath {
     }
 }
 
-fn movement_system(mut q: Query<(&mut MovementPath, &mut Transform, &mut LinearVelocity)>, timer: Res<Time>) {
-    for (mut path, mut transform, mut velocity) in q.iter_mut() {
-        if !path.0.is_empty() {
-            if (transform.translation - path.0[0]).length() <= 1.2 {
-                info!("Reach destination");
-                velocity.0 = Vec3::ZERO;
-                path.remove_first();
-            } else {
-                let mut direction = (path.0[0] - transform.translation).normalize(); // Get the first direction
-                direction.y = 0.;
-                let speed = 10.;
-                velocity.0 = direction * speed;
-                // Calculate the movement based on speed and time
-                //transform.translation += direction * speed * timer.delta_seconds();
+/// How a unit moves along its `MovementPath`.
+#[derive(Component, Clone, Copy, Debug, Reflect, Deserialize)]
+pub struct MovementStats {
+    pub max_speed: f32,
+    /// Change in speed per second, both speeding up and braking.
+    pub acceleration: f32,
+    /// Radians per second the unit turns towards its heading.
+    pub turn_rate: f32,
+    /// Distance at which a waypoint counts as reached.
+    pub arrival_radius: f32,
+}
+
+impl Default for MovementStats {
+    fn default() -> Self {
+        Self {
+            max_speed: 10.,
+            acceleration: 25.,
+            turn_rate: 10.,
+            arrival_radius: 0.5,
+        }
+    }
+}
+
+/// When a unit counts as stuck and how hard it tries to get free.
+#[derive(Resource, Clone, Copy, Debug, Reflect)]
+pub struct StuckSettings {
+    /// Seconds over which progress towards the goal is measured.
+    pub window: f32,
+    /// Distance the unit has to get closer to its goal within a window to not count as stuck.
+    pub min_progress: f32,
+    /// Recovery attempts before the unit gives up on its path.
+    pub max_attempts: u32,
+}
+
+impl Default for StuckSettings {
+    fn default() -> Self {
+        Self {
+            window: 1.,
+            min_progress: 0.5,
+            max_attempts: 3,
+        }
+    }
+}
+
+impl StuckSettings {
+    /// Recovery for the nth time in a row the unit got stuck: alternate repathing and nudging
+    /// sideways, then give up.
+    pub fn recovery(&self, attempts: u32) -> StuckRecovery {
+        if attempts > self.max_attempts {
+            StuckRecovery::GiveUp
+        } else if attempts % 2 == 1 {
+            StuckRecovery::Repath
+        } else {
+            StuckRecovery::Nudge
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StuckRecovery {
+    /// Path to the same goal again from where the unit is.
+    Repath,
+    /// Push the unit sideways to slide it off whatever it's pressed against.
+    Nudge,
+    /// Clear the path and stop.
+    GiveUp,
+}
+
+/// Sent when a unit following a path made too little progress over the last window.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct UnitStuck {
+    pub entity: Entity,
+    pub attempts: u32,
+    pub recovery: StuckRecovery,
+}
+
+/// Progress of a unit towards its goal, measured once per `StuckSettings::window`.
+#[derive(Component, Default, Reflect)]
+pub struct StuckTracker {
+    timer: Timer,
+    goal: Option<Vec3>,
+    last_distance: Option<f32>,
+    attempts: u32,
+}
+
+impl StuckTracker {
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    fn reset(&mut self, goal: Option<Vec3>, window: f32) {
+        self.timer = Timer::from_seconds(window, TimerMode::Repeating);
+        self.goal = goal;
+        self.last_distance = None;
+        self.attempts = 0;
+    }
+}
+
+/// Length of the path left to walk from `position`, on the ground plane.
+pub fn remaining_distance(position: Vec3, path: &[Vec3]) -> f32 {
+    let Some(next) = path.first() else {
+        return 0.;
+    };
+    let flat = |v: Vec3| Vec3::new(v.x, 0., v.z);
+    flat(*next - position).length()
+        + path.windows(2).map(|segment| flat(segment[1] - segment[0]).length()).sum::<f32>()
+}
+
+/// Velocity on the ground plane to aim for: full speed through corners, braking only so the unit
+/// comes to rest at the last waypoint.
+pub fn desired_velocity(position: Vec3, path: &[Vec3], stats: &MovementStats) -> Vec3 {
+    let Some(next) = path.first() else {
+        return Vec3::ZERO;
+    };
+    let remaining = remaining_distance(position, path);
+    let speed = stats.max_speed.min((2. * stats.acceleration * remaining).sqrt());
+    Vec3::new(next.x - position.x, 0., next.z - position.z).normalize_or_zero() * speed
+}
+
+/// Moves `current` towards `target` by at most `max_delta`.
+pub fn move_towards(current: Vec3, target: Vec3, max_delta: f32) -> Vec3 {
+    let difference = target - current;
+    if difference.length() <= max_delta {
+        target
+    } else {
+        current + difference.normalize() * max_delta
+    }
+}
+
+/// Turns `rotation` towards facing `heading` around the Y axis by at most `max_angle` radians.
+pub fn turn_towards(rotation: Quat, heading: Vec3, max_angle: f32) -> Quat {
+    let target = Quat::from_rotation_y(f32::atan2(-heading.x, -heading.z));
+    let angle = rotation.angle_between(target);
+    if angle <= max_angle {
+        target
+    } else {
+        rotation.slerp(target, max_angle / angle)
+    }
+}
+
+fn movement_system(
+    mut q: Query<(&mut MovementPath, &MovementStats, &Transform, &mut Rotation, &mut LinearVelocity)>,
+    timer: Res<Time>,
+) {
+    let delta = timer.delta_seconds();
+    for (mut path, stats, transform, mut rotation, mut velocity) in q.iter_mut() {
+        if path.0.is_empty() {
+            continue;
+        }
+        // Intermediate waypoints are passed through; only the last one stops the unit.
+        while path.0.first().is_some_and(|waypoint| {
+            Vec3::new(waypoint.x - transform.translation.x, 0., waypoint.z - transform.translation.z)
+                .length() <= stats.arrival_radius
+        }) {
+            path.remove_first();
+        }
+        if path.0.is_empty() {
+            info!("Reach destination");
+            velocity.0 = Vec3::new(0., velocity.0.y, 0.);
+            continue;
+        }
+
+        let desired = desired_velocity(transform.translation, &path.0, stats);
+        let current = Vec3::new(velocity.0.x, 0., velocity.0.z);
+        let steered = move_towards(current, desired, stats.acceleration * delta);
+        velocity.0 = Vec3::new(steered.x, velocity.0.y, steered.z);
+        if steered.length_squared() > f32::EPSILON {
+            rotation.0 = turn_towards(rotation.0, steered, stats.turn_rate * delta);
+        }
+    }
+}
+
+// Compare the distance left to the goal once per window; too little progress means the unit is stuck.
+fn stuck_detection_system(
+    time: Res<Time>,
+    settings: Res<StuckSettings>,
+    mut units_q: Query<(Entity, &MovementPath, &Transform, &mut StuckTracker)>,
+    mut stuck_events: EventWriter<UnitStuck>,
+) {
+    for (entity, path, transform, mut tracker) in units_q.iter_mut() {
+        if path.is_empty() || path.goal() != tracker.goal {
+            tracker.reset(path.goal(), settings.window);
+            continue;
+        }
+        if !tracker.timer.tick(time.delta()).just_finished() {
+            continue;
+        }
+        let distance = remaining_distance(transform.translation, &path.0);
+        let progressed = tracker.last_distance.map_or(true, |last| last - distance >= settings.min_progress);
+        tracker.last_distance = Some(distance);
+        if progressed {
+            tracker.attempts = 0;
+            continue;
+        }
+        tracker.attempts += 1;
+        let recovery = settings.recovery(tracker.attempts);
+        warn!("{:?} is stuck, attempt {}: {:?}", entity, tracker.attempts, recovery);
+        stuck_events.send(UnitStuck {
+            entity,
+            attempts: tracker.attempts,
+            recovery,
+        });
+    }
+}
+
+// Nudging and giving up happen here; repathing is up to the pathfinding plugin.
+fn recover_stuck_units(
+    mut stuck_events: EventReader<UnitStuck>,
+    mut units_q: Query<(&mut MovementPath, &MovementStats, &Transform, &mut LinearVelocity)>,
+) {
+    for event in stuck_events.iter() {
+        let Ok((mut path, stats, transform, mut velocity)) = units_q.get_mut(event.entity) else {
+            continue;
+        };
+        match event.recovery {
+            StuckRecovery::Repath => {}
+            StuckRecovery::Nudge => {
+                let Some(next) = path.0.first() else {
+                    continue;
+                };
+                let forward = Vec3::new(next.x - transform.translation.x, 0., next.z - transform.translation.z)
+                    .normalize_or_zero();
+                // Alternate sides in case the first one is blocked too.
+                let side = if event.attempts % 4 == 2 { 1. } else { -1. };
+                let sideways = Vec3::Y.cross(forward) * side * stats.max_speed;
+                velocity.0 = Vec3::new(sideways.x, velocity.0.y, sideways.z);
+            }
+            StuckRecovery::GiveUp => {
+                *path = MovementPath::default();
+                velocity.0 = Vec3::new(0., velocity.0.y, 0.);
             }
         }
     }
+}
 
+#[cfg(test)]
+mod movement_test {
+    use std::time::Duration;
+    use bevy::prelude::*;
+    use bevy::time::TimeUpdateStrategy;
+    use bevy_xpbd_3d::prelude::{LinearVelocity, Rotation};
+    use crate::game_state::{AppState, MatchState};
+    use crate::movement::{desired_velocity, move_towards, turn_towards, MovementPath, MovementPlugin, MovementStats};
+    use crate::movement::{StuckRecovery, StuckSettings, StuckTracker, UnitStuck};
+
+    #[test]
+    fn it_slows_down_into_the_final_waypoint() {
+        let stats = MovementStats::default();
+        let far = desired_velocity(Vec3::ZERO, &[Vec3::new(0., 0., 20.)], &stats);
+        let near = desired_velocity(Vec3::ZERO, &[Vec3::new(0., 0., 1.)], &stats);
+        assert!((far.length() - stats.max_speed).abs() < 1e-4);
+        assert!(near.length() < far.length());
+    }
+
+    #[test]
+    fn it_keeps_full_speed_towards_intermediate_waypoints() {
+        let stats = MovementStats::default();
+        let path = [Vec3::new(0., 0., 1.), Vec3::new(20., 0., 1.)];
+        let velocity = desired_velocity(Vec3::ZERO, &path, &stats);
+        assert!((velocity.length() - stats.max_speed).abs() < 1e-4);
+        assert!(velocity.x.abs() < 1e-5);
+    }
+
+    #[test]
+    fn it_ignores_height_difference_to_waypoint() {
+        let stats = MovementStats::default();
+        let velocity = desired_velocity(Vec3::new(0., 0.8, 0.), &[Vec3::new(0., 0., 20.)], &stats);
+        assert_eq!(velocity.y, 0.);
+    }
+
+    #[test]
+    fn it_limits_change_in_velocity_to_acceleration() {
+        let velocity = move_towards(Vec3::ZERO, Vec3::new(0., 0., 10.), 2.);
+        assert_eq!(velocity, Vec3::new(0., 0., 2.));
+    }
+
+    #[test]
+    fn it_turns_at_most_turn_rate() {
+        let rotation = turn_towards(Quat::IDENTITY, Vec3::X, 0.1);
+        assert!((rotation.angle_between(Quat::IDENTITY) - 0.1).abs() < 1e-4);
+    }
+
+    #[test]
+    fn it_faces_heading_once_turned() {
+        let rotation = turn_towards(Quat::IDENTITY, Vec3::X, 10.);
+        assert!((rotation * Vec3::NEG_Z).distance(Vec3::X) < 1e-4);
+    }
+
+    #[test]
+    fn it_escalates_stuck_recovery_until_giving_up() {
+        let settings = StuckSettings::default();
+        assert_eq!(settings.recovery(1), StuckRecovery::Repath);
+        assert_eq!(settings.recovery(2), StuckRecovery::Nudge);
+        assert_eq!(settings.recovery(settings.max_attempts + 1), StuckRecovery::GiveUp);
+    }
+
+    #[test]
+    fn it_gives_up_on_path_when_unit_cant_move() {
+        let mut app = App::new();
+        app
+            .add_state::<AppState>()
+            .add_state::<MatchState>()
+            .insert_resource(NextState(Some(AppState::InGame)))
+            .add_plugins((MinimalPlugins, MovementPlugin))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(250)));
+        // Without physics nothing moves the unit, as if it were pressed against a wall.
+        let unit = app.world.spawn((
+            TransformBundle::default(),
+            MovementPath::new(vec![Vec3::new(0., 0., 20.)]),
+            MovementStats::default(),
+            StuckTracker::default(),
+            Rotation::default(),
+            LinearVelocity::default(),
+        )).id();
+        let mut reader = app.world.resource::<Events<UnitStuck>>().get_reader();
+        let mut stuck = vec![];
+        for _ in 0..40 {
+            app.update();
+            let events = app.world.resource::<Events<UnitStuck>>();
+            stuck.extend(reader.iter(events).map(|event| event.recovery));
+        }
+
+        assert_eq!(stuck.first(), Some(&StuckRecovery::Repath));
+        assert_eq!(stuck.last(), Some(&StuckRecovery::GiveUp));
+        assert!(app.world.get::<MovementPath>(unit).unwrap().is_empty());
+    }
 }
@@ -0,0 +1,388 @@
+use std::collections::VecDeque;
+use bevy::prelude::*;
+use bevy_mod_picking::prelude::{Down, Pointer, PointerButton};
+use bevy_xpbd_3d::prelude::LinearVelocity;
+use oxidized_navigation::debug_draw::DrawPath;
+use crate::formation::{cycle_formation_system, FormationSettings};
+use crate::game_state::{match_running, AppState, MatchEntity};
+use crate::harvest::{GoldMine, Worker};
+use crate::input::{ActionState, CursorPosition, InputAction, InputBindings};
+use crate::movement::MovementPath;
+use crate::pathfinding::{AsyncPathfindingTasks, PathfindingSet, PathRequest};
+use crate::selection::{selection_input_system, Selected};
+
+pub struct OrdersPlugin;
+
+impl Plugin for OrdersPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<MoveEvent>()
+            .register_type::<FormationSettings>()
+            .register_type::<OrderQueue>()
+            .register_type::<HoldPosition>()
+            .init_resource::<FormationSettings>()
+            .init_resource::<OrderTargeting>()
+            .add_systems(
+                Update,
+                (
+                    (
+                        move_action_system,
+                        targeted_order_system.after(selection_input_system),
+                        stop_action_system,
+                        issue_move_orders,
+                        advance_order_queue,
+                    )
+                        .chain()
+                        .in_set(PathfindingSet::Request),
+                    draw_order_queue,
+                    cycle_formation_system,
+                ).run_if(match_running))
+            .add_systems(OnExit(AppState::GameOver), reset_order_targeting);
+    }
+}
+
+/// Orders the selected units to `target`, after their current orders if `queue` is set.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct MoveEvent {
+    pub target: Vec3,
+    pub queue: bool,
+    /// Entity the order was given on, such as a gold mine, picked where the cursor ray hit it.
+    pub entity: Option<Entity>,
+}
+
+/// Set when an order is waiting for its target, such as after pressing Move on the command card:
+/// the next Select on the ground sends the selection there instead of selecting.
+#[derive(Resource, Default)]
+pub struct OrderTargeting(pub bool);
+
+/// Run condition for systems that shouldn't react to clicks meant as an order's target.
+pub fn targeting_order(targeting: Option<Res<OrderTargeting>>) -> bool {
+    targeting.is_some_and(|targeting| targeting.0)
+}
+
+/// Keeps a unit in place: it drops its orders and doesn't take queued ones until ordered again.
+#[derive(Component, Default, Reflect)]
+pub struct HoldPosition;
+
+/// Move orders queued with shift, each pathfound once the unit finishes the one before it.
+#[derive(Component, Default, Reflect)]
+pub struct OrderQueue {
+    orders: VecDeque<Vec3>,
+    preview: Option<Entity>,
+}
+
+impl OrderQueue {
+    pub fn push(&mut self, destination: Vec3) {
+        self.orders.push_back(destination);
+    }
+
+    pub fn pop(&mut self) -> Option<Vec3> {
+        self.orders.pop_front()
+    }
+
+    pub fn clear(&mut self) {
+        self.orders.clear();
+    }
+
+    pub fn last(&self) -> Option<Vec3> {
+        self.orders.back().copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.orders.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Vec3> {
+        self.orders.iter()
+    }
+}
+
+/// Entity pressed with one of `action`'s mouse buttons this frame, if any.
+fn pressed_entity(bindings: &InputBindings, action: InputAction, downs: &mut EventReader<Pointer<Down>>) -> Option<Entity> {
+    let buttons: Vec<PointerButton> = bindings.get(action)
+        .iter()
+        .filter_map(|binding| binding.pointer_button())
+        .collect();
+    downs.iter()
+        .filter(|down| buttons.contains(&down.button))
+        .map(|down| down.target)
+        .last()
+}
+
+// Move and attack-move both send the selection to the ground point under the cursor.
+// There is no combat yet, so attack-move behaves like a plain move.
+fn move_action_system(
+    actions: Res<ActionState>,
+    bindings: Res<InputBindings>,
+    cursor: Res<CursorPosition>,
+    mut downs: EventReader<Pointer<Down>>,
+    mut move_events: EventWriter<MoveEvent>,
+) {
+    let entity = pressed_entity(&bindings, InputAction::Move, &mut downs);
+    if !actions.just_pressed(InputAction::Move) && !actions.just_pressed(InputAction::AttackMove) {
+        return;
+    }
+    if let Some(target) = cursor.ground {
+        move_events.send(MoveEvent {
+            target,
+            queue: actions.pressed(InputAction::Queue),
+            entity,
+        });
+    }
+}
+
+// Select on the ground sends a targeted order like a move; holding queue keeps targeting for the next one.
+fn targeted_order_system(
+    actions: Res<ActionState>,
+    bindings: Res<InputBindings>,
+    cursor: Res<CursorPosition>,
+    mut targeting: ResMut<OrderTargeting>,
+    mut downs: EventReader<Pointer<Down>>,
+    mut move_events: EventWriter<MoveEvent>,
+) {
+    let entity = pressed_entity(&bindings, InputAction::Select, &mut downs);
+    if !targeting.0 {
+        return;
+    }
+    if actions.just_pressed(InputAction::Cancel) || actions.just_pressed(InputAction::Move) {
+        targeting.0 = false;
+        return;
+    }
+    if !actions.just_pressed(InputAction::Select) {
+        return;
+    }
+    let Some(target) = cursor.ground else {
+        return;
+    };
+    let queue = actions.pressed(InputAction::Queue);
+    move_events.send(MoveEvent { target, queue, entity });
+    if !queue {
+        targeting.0 = false;
+    }
+}
+
+fn reset_order_targeting(mut targeting: ResMut<OrderTargeting>) {
+    targeting.0 = false;
+}
+
+// Stop and hold cancel the selection's path, queued orders and any path request in flight.
+fn stop_action_system(
+    mut commands: Commands,
+    actions: Res<ActionState>,
+    mut pathfinding_tasks: ResMut<AsyncPathfindingTasks>,
+    mut selected_q: Query<(Entity, &mut MovementPath, &mut LinearVelocity, Option<&mut OrderQueue>), With<Selected>>,
+) {
+    let hold = actions.just_pressed(InputAction::Hold);
+    if !hold && !actions.just_pressed(InputAction::Stop) {
+        return;
+    }
+    for (entity, mut path, mut velocity, order_queue) in selected_q.iter_mut() {
+        *path = MovementPath::default();
+        velocity.0 = Vec3::new(0., velocity.0.y, 0.);
+        pathfinding_tasks.cancel(entity);
+        if let Some(mut order_queue) = order_queue {
+            order_queue.clear();
+        }
+        if hold {
+            commands.entity(entity).insert(HoldPosition);
+        } else {
+            commands.entity(entity).remove::<HoldPosition>();
+        }
+    }
+}
+
+// Turn a move order into one destination per selected unit around its own formation slot.
+// Queued orders on busy units are appended instead of replacing their current orders.
+// Workers ordered onto a mine are left out, as `gather_order_system` sends them to it instead.
+pub(crate) fn issue_move_orders(
+    mut commands: Commands,
+    mut move_events: EventReader<MoveEvent>,
+    formation: Res<FormationSettings>,
+    pathfinding_tasks: Res<AsyncPathfindingTasks>,
+    mut selected_q: Query<(Entity, &Transform, &MovementPath, Option<&mut OrderQueue>), With<Selected>>,
+    mines_q: Query<&GoldMine>,
+    workers_q: Query<(), With<Worker>>,
+    mut path_requests: EventWriter<PathRequest>,
+) {
+    for event in move_events.iter() {
+        let (target, queue) = (event.target, event.queue);
+        let gathering = event.entity
+            .and_then(|entity| mines_q.get(entity).ok())
+            .is_some_and(|mine| !mine.is_depleted());
+        // Queued formations are planned from where each unit will be once its queue is done.
+        let units: Vec<(Entity, Vec3)> = selected_q.iter()
+            .filter(|(entity, ..)| !(gathering && workers_q.contains(*entity)))
+            .map(|(entity, transform, path, order_queue)| {
+                let position = if queue {
+                    order_queue.and_then(|order_queue| order_queue.last())
+                        .or(path.goal())
+                        .unwrap_or(transform.translation)
+                } else {
+                    transform.translation
+                };
+                (entity, position)
+            })
+            .collect();
+        for (entity, goal) in formation.plan(target, &units) {
+            let Ok((_, _, path, order_queue)) = selected_q.get_mut(entity) else {
+                continue;
+            };
+            commands.entity(entity).remove::<HoldPosition>();
+            let busy = !path.is_empty() || pathfinding_tasks.is_pending(entity);
+            match order_queue {
+                Some(mut order_queue) if queue && (busy || !order_queue.is_empty()) => {
+                    order_queue.push(goal);
+                }
+                order_queue => {
+                    if let Some(mut order_queue) = order_queue {
+                        order_queue.clear();
+                    }
+                    path_requests.send(PathRequest::new(entity, goal));
+                }
+            }
+        }
+    }
+}
+
+fn advance_order_queue(
+    pathfinding_tasks: Res<AsyncPathfindingTasks>,
+    mut units_q: Query<(Entity, &MovementPath, &mut OrderQueue), Without<HoldPosition>>,
+    mut path_requests: EventWriter<PathRequest>,
+) {
+    for (entity, path, mut order_queue) in units_q.iter_mut() {
+        if order_queue.is_empty() || !path.is_empty() || pathfinding_tasks.is_pending(entity) {
+            continue;
+        }
+        if let Some(destination) = order_queue.pop() {
+            path_requests.send(PathRequest::new(entity, destination));
+        }
+    }
+}
+
+// Draw a line from each selected unit through its current and queued destinations.
+fn draw_order_queue(
+    mut commands: Commands,
+    mut units_q: Query<(&Transform, &MovementPath, &mut OrderQueue, Option<&Selected>)>,
+    mut draw_path_q: Query<&mut DrawPath>,
+) {
+    for (transform, path, mut order_queue, selected) in units_q.iter_mut() {
+        let show = selected.is_some() && !order_queue.is_empty();
+        match (show, order_queue.preview) {
+            (false, Some(preview)) => {
+                commands.entity(preview).despawn();
+                order_queue.preview = None;
+            }
+            (true, preview) => {
+                let pulled_path: Vec<Vec3> = std::iter::once(transform.translation)
+                    .chain(path.goal())
+                    .chain(order_queue.iter().copied())
+                    .collect();
+                match preview.and_then(|preview| draw_path_q.get_mut(preview).ok()) {
+                    Some(mut draw_path) => draw_path.pulled_path = pulled_path,
+                    None => {
+                        order_queue.preview = Some(commands.spawn((
+                            DrawPath {
+                                timer: None,
+                                pulled_path,
+                                color: Color::GREEN,
+                            },
+                            MatchEntity,
+                        )).id());
+                    }
+                }
+            }
+            (false, None) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod orders_test {
+    use bevy::input::InputPlugin;
+    use bevy::prelude::*;
+    use bevy_mod_picking::prelude::{Down, Pointer};
+    use crate::game_state::{AppState, MatchState};
+    use crate::harvest::{GoldMine, Worker};
+    use crate::input::{ActionState, CursorPosition, InputAction, InputBindings};
+    use crate::movement::MovementPath;
+    use crate::orders::{MoveEvent, OrderQueue, OrdersPlugin, OrderTargeting};
+    use crate::pathfinding::{AsyncPathfindingTasks, PathRequest};
+    use crate::selection::Selected;
+
+    fn setup() -> App {
+        let mut app = App::new();
+        app
+            .add_state::<AppState>()
+            .add_state::<MatchState>()
+            .insert_resource(NextState(Some(AppState::InGame)))
+            .add_plugins((MinimalPlugins, InputPlugin, OrdersPlugin))
+            .add_event::<PathRequest>()
+            .add_event::<Pointer<Down>>()
+            .init_resource::<ActionState>()
+            .init_resource::<InputBindings>()
+            .init_resource::<CursorPosition>()
+            .insert_resource(AsyncPathfindingTasks::default());
+        app
+    }
+
+    fn sent_requests(app: &App) -> Vec<PathRequest> {
+        let events = app.world.resource::<Events<PathRequest>>();
+        events.get_reader().iter(events).copied().collect()
+    }
+
+    #[test]
+    fn it_pathfinds_next_queued_order_when_idle() {
+        let mut app = setup();
+        let mut order_queue = OrderQueue::default();
+        order_queue.push(Vec3::X);
+        order_queue.push(Vec3::Z);
+        let entity = app.world.spawn((TransformBundle::default(), MovementPath::default(), order_queue)).id();
+        app.update();
+
+        let requests = sent_requests(&app);
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].entity, entity);
+        assert_eq!(requests[0].goal, Vec3::X);
+        assert_eq!(app.world.get::<OrderQueue>(entity).unwrap().iter().collect::<Vec<_>>(), vec![&Vec3::Z]);
+    }
+
+    #[test]
+    fn it_waits_for_current_path_before_next_queued_order() {
+        let mut app = setup();
+        let mut order_queue = OrderQueue::default();
+        order_queue.push(Vec3::X);
+        app.world.spawn((TransformBundle::default(), MovementPath::new(vec![Vec3::Z]), order_queue));
+        app.update();
+
+        assert!(sent_requests(&app).is_empty());
+    }
+
+    #[test]
+    fn it_orders_the_selection_to_a_targeted_click() {
+        let mut app = setup();
+        let entity = app.world.spawn((TransformBundle::default(), MovementPath::default(), Selected)).id();
+        app.world.insert_resource(OrderTargeting(true));
+        app.world.resource_mut::<CursorPosition>().ground = Some(Vec3::X);
+        app.world.resource_mut::<ActionState>().trigger(InputAction::Select);
+        app.update();
+
+        let requests = sent_requests(&app);
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].entity, entity);
+        assert!(!app.world.resource::<OrderTargeting>().0);
+    }
+
+    #[test]
+    fn it_leaves_workers_ordered_onto_a_mine_out_of_the_formation() {
+        let mut app = setup();
+        let mine = app.world.spawn((TransformBundle::from(Transform::from_xyz(10., 1., 0.)), GoldMine::new(100))).id();
+        app.world.spawn((TransformBundle::default(), MovementPath::default(), Worker::default(), Selected));
+        let soldier = app.world.spawn((TransformBundle::default(), MovementPath::default(), Selected)).id();
+        app.world.send_event(MoveEvent { target: Vec3::new(12., 0., 0.), queue: false, entity: Some(mine) });
+        app.update();
+
+        let requests = sent_requests(&app);
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].entity, soldier);
+    }
+}
@@ -7,12 +7,11 @@ use oxidized_navigation::debug_draw::{DrawNavMesh, DrawPath, OxidizedNavigationD
 use bevy::prelude::*;
 use bevy::math::Vec3;
 use bevy::tasks::{AsyncComputeTaskPool, Task};
+use bevy::utils::{HashMap, HashSet};
 use oxidized_navigation::query::find_path;
 use oxidized_navigation::tiles::NavMeshTiles;
-use bevy_mod_picking::prelude::ListenerInput;
-use bevy_mod_picking::events::{Down, Pointer};
-use crate::movement::MovementPath;
-use crate::world::Selected;
+use crate::game_speed::time_running;
+use crate::movement::{MovementPath, StuckRecovery, UnitStuck};
 
 pub struct PathfindingPlugin {
     config: NavMeshSettings,
@@ -48,17 +47,36 @@ impl Plugin for PathfindingPlugin {
             OxidizedNavigationDebugDrawPlugin,
         ))
 
-            .add_event::<MoveEvent>()
+            .add_event::<PathRequest>()
+            .add_event::<PathResult>()
+            .add_event::<UnitStuck>()
+            .register_type::<ActivePathRequest>()
+            .register_type::<PathGoal>()
             .insert_resource(AsyncPathfindingTasks::default())
+            .configure_sets(Update, (PathfindingSet::Request, PathfindingSet::Process).chain())
+            // Finished paths wait to be applied until the game is unpaused.
+            .configure_sets(Update, PathfindingSet::Process.run_if(time_running))
             .add_systems(
                 Update, (
-                    run_async_pathfinding,
-                    poll_pathfinding_tasks_system,
-                    toggle_nav_mesh_system
+                    (
+                        run_async_pathfinding,
+                        poll_pathfinding_tasks_system,
+                        (fallback_to_nearest_reachable, spawn_unreachable_markers),
+                    ).chain().in_set(PathfindingSet::Process),
+                    (repath_on_nav_mesh_change, repath_stuck_units).in_set(PathfindingSet::Request),
+                    toggle_nav_mesh_system,
                 ));
     }
 }
 
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PathfindingSet {
+    /// Systems sending `PathRequest`s, so they're picked up in the same frame.
+    Request,
+    /// Spawning path tasks and polling their results.
+    Process,
+}
+
 //  Async Pathfinding.
 //  Press A to run.
 //
@@ -66,65 +84,301 @@ impl Plugin for PathfindingPlugin {
 //  Also check out Bevy's async compute example.
 //  https://github.com/bevyengine/bevy/blob/main/examples/async_tasks/async_compute.rs
 //
-// Holder resource for tasks.
+// Holder resource for tasks, at most one in flight per entity.
+// Replacing an entity's task drops the old one, which cancels it.
 #[derive(Default, Resource)]
-struct AsyncPathfindingTasks {
-    tasks: Vec<(Entity, Task<Option<Vec<Vec3>>>)>,
+pub struct AsyncPathfindingTasks {
+    next_id: u64,
+    tasks: HashMap<Entity, PendingPath>,
+}
+
+struct PendingPath {
+    request: PathRequest,
+    id: PathRequestId,
+    task: Task<PathOutcome>,
+}
+
+impl AsyncPathfindingTasks {
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    pub fn is_pending(&self, entity: Entity) -> bool {
+        self.tasks.contains_key(&entity)
+    }
+
+    /// Drops the entity's request in flight, if any, which cancels its task.
+    pub fn cancel(&mut self, entity: Entity) {
+        self.tasks.remove(&entity);
+    }
+
+    fn next_id(&mut self) -> PathRequestId {
+        let id = PathRequestId(self.next_id);
+        self.next_id += 1;
+        id
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Reflect)]
+pub struct PathRequestId(u64);
+
+/// Id of the latest path request issued for an entity. Results of any other request are stale.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+pub struct ActivePathRequest(pub PathRequestId);
+
+/// Where the unit's current `MovementPath` leads, kept to path there again if the nav mesh changes.
+#[derive(Component, Clone, Copy, Debug, Reflect)]
+pub struct PathGoal {
+    pub goal: Vec3,
+    pub search_radius: Option<f32>,
+}
+
+/// Radius searched for the closest point on the nav mesh when the goal itself can't be reached.
+const FALLBACK_SEARCH_RADIUS: f32 = 15.;
+
+/// Asks for `entity` to path from where it currently is to `goal`, superseding any request
+/// still in flight for it.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct PathRequest {
+    pub entity: Entity,
+    pub goal: Vec3,
+    /// How far from the start and goal to look for the nav mesh, `None` for oxidized_navigation's default.
+    pub search_radius: Option<f32>,
+}
+
+impl PathRequest {
+    pub fn new(entity: Entity, goal: Vec3) -> Self {
+        Self {
+            entity,
+            goal,
+            search_radius: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum PathOutcome {
+    Found(Vec<Vec3>),
+    /// The nav mesh query failed; its error is logged when it happens.
+    Failed,
+    /// Pathfinding succeeded but there is nothing to walk, or the nav mesh couldn't be read.
+    NoPath,
+}
+
+/// Sent when a path request that hasn't been superseded finishes.
+#[derive(Event, Debug)]
+pub struct PathResult {
+    pub entity: Entity,
+    pub request: PathRequest,
+    pub outcome: PathOutcome,
+}
+
+impl PathResult {
+    pub fn is_found(&self) -> bool {
+        matches!(self.outcome, PathOutcome::Found(_))
+    }
 }
 
 // Queue up pathfinding tasks.
 fn run_async_pathfinding(
-    mut move_events: EventReader<MoveEvent>,
+    mut commands: Commands,
+    mut path_requests: EventReader<PathRequest>,
     nav_mesh_settings: Res<NavMeshSettings>,
     nav_mesh: Res<NavMesh>,
-    selected_q: Query<(&Transform, Entity), With<Selected>>,
+    transform_q: Query<&Transform>,
     mut pathfinding_task: ResMut<AsyncPathfindingTasks>,
 ) {
-    for event in move_events.iter() {
-        for (selected, entity) in selected_q.iter() {
-            let thread_pool = AsyncComputeTaskPool::get();
-            let nav_mesh_lock = nav_mesh.get();
-            let task = thread_pool.spawn(async_path_find(
-                nav_mesh_lock,
-                nav_mesh_settings.clone(),
-                selected.translation,
-                event.0.unwrap(),
-                None,
-            ));
-            pathfinding_task.tasks.push((entity, task));
-        }
+    for request in path_requests.iter() {
+        let Ok(transform) = transform_q.get(request.entity) else {
+            continue;
+        };
+        let thread_pool = AsyncComputeTaskPool::get();
+        let nav_mesh_lock = nav_mesh.get();
+        let task = thread_pool.spawn(async_path_find(
+            nav_mesh_lock,
+            nav_mesh_settings.clone(),
+            transform.translation,
+            request.goal,
+            request.search_radius,
+        ));
+        let id = pathfinding_task.next_id();
+        pathfinding_task.tasks.insert(request.entity, PendingPath { request: *request, id, task });
+        commands.entity(request.entity).insert(ActivePathRequest(id));
     }
 }
 
 fn poll_pathfinding_tasks_system(
     mut commands: Commands,
     mut pathfinding_task: ResMut<AsyncPathfindingTasks>,
+    entities_q: Query<Option<&ActivePathRequest>>,
+    mut path_results: EventWriter<PathResult>,
 ) {
-    // Go through and remove completed tasks.
-    pathfinding_task.tasks.retain_mut(|(entity, task)| {
-        if let Some(mut string_path) = future::block_on(future::poll_once(task)).unwrap_or(None) {
+    // Go through and remove completed tasks, dropping the ones whose entity is gone.
+    let mut finished = vec![];
+    pathfinding_task.tasks.retain(|entity, pending| {
+        let Ok(active_request) = entities_q.get(*entity) else {
+            return false;
+        };
+        let Some(outcome) = future::block_on(future::poll_once(&mut pending.task)) else {
+            return true;
+        };
+        if active_request.is_some_and(|active_request| active_request.0 != pending.id) {
+            info!("Dropping stale path result {:?} for {:?}", pending.id, entity);
+            return false;
+        }
+        finished.push(PathResult {
+            entity: *entity,
+            request: pending.request,
+            outcome,
+        });
+        false
+    });
+
+    for result in finished {
+        if let PathOutcome::Found(string_path) = &result.outcome {
             info!("Async path task finished with result: {:?}", string_path);
             commands.spawn(DrawPath {
                 timer: Some(Timer::from_seconds(4.0, TimerMode::Once)),
                 pulled_path: string_path.clone(),
                 color: Color::BLUE,
             });
-            string_path.remove(0);
-            let path = MovementPath::new(string_path);
-            commands.entity(*entity).insert(path);
-            false
-        } else {
-            true
+            let path = MovementPath::new(string_path[1..].to_vec());
+            commands.entity(result.entity).insert((path, PathGoal {
+                goal: result.request.goal,
+                search_radius: result.request.search_radius,
+            }));
         }
-    });
+        path_results.send(result);
+    }
+}
+
+// Retry failed requests once, letting the nav mesh query snap the goal to the closest reachable point.
+fn fallback_to_nearest_reachable(
+    mut path_results: EventReader<PathResult>,
+    mut path_requests: EventWriter<PathRequest>,
+) {
+    for result in path_results.iter() {
+        if result.is_found() || result.request.search_radius.is_some() {
+            continue;
+        }
+        path_requests.send(PathRequest {
+            search_radius: Some(FALLBACK_SEARCH_RADIUS),
+            ..result.request
+        });
+    }
+}
+
+// Show a red "can't go there" ring where a unit was ordered to but couldn't path to.
+fn spawn_unreachable_markers(mut commands: Commands, mut path_results: EventReader<PathResult>) {
+    for result in path_results.iter() {
+        if result.is_found() || result.request.search_radius.is_some() {
+            continue;
+        }
+        warn!("{:?} can't reach {:?}: {:?}", result.entity, result.request.goal, result.outcome);
+        let center = result.request.goal + Vec3::Y * 0.1;
+        commands.spawn(DrawPath {
+            timer: Some(Timer::from_seconds(1.5, TimerMode::Once)),
+            pulled_path: (0..=16)
+                .map(|i| {
+                    let angle = std::f32::consts::TAU * i as f32 / 16.;
+                    center + Vec3::new(angle.cos(), 0., angle.sin()) * 0.75
+                })
+                .collect(),
+            color: Color::RED,
+        });
+    }
+}
+
+/// Nav mesh tile containing `position`, matching oxidized_navigation's tiling.
+fn tile_containing(settings: &NavMeshSettings, position: Vec3) -> UVec2 {
+    let tile_size = settings.cell_width * settings.tile_width as f32;
+    let offset = Vec2::new(position.x, position.z) + settings.world_half_extents;
+    (offset / tile_size).floor().max(Vec2::ZERO).as_uvec2()
+}
+
+/// Tiles the path from `start` through `waypoints` crosses.
+pub fn path_tiles(settings: &NavMeshSettings, start: Vec3, waypoints: &[Vec3]) -> HashSet<UVec2> {
+    // Sampling at a quarter tile can't skip over a whole tile.
+    let step = settings.cell_width * settings.tile_width as f32 / 4.;
+    let mut tiles = HashSet::new();
+    tiles.insert(tile_containing(settings, start));
+    let mut from = start;
+    for to in waypoints {
+        let samples = (from.distance(*to) / step).ceil().max(1.) as usize;
+        for i in 1..=samples {
+            tiles.insert(tile_containing(settings, from.lerp(*to, i as f32 / samples as f32)));
+        }
+        from = *to;
+    }
+    tiles
 }
 
-#[derive(Event)]
-pub struct MoveEvent(Option<Vec3>);
+// When obstacles come or go, oxidized_navigation regenerates the tiles around them. Units whose
+// remaining path crosses a regenerated tile path to their goal again from where they are.
+fn repath_on_nav_mesh_change(
+    nav_mesh: Res<NavMesh>,
+    nav_mesh_settings: Res<NavMeshSettings>,
+    mut known_generations: Local<HashMap<UVec2, u64>>,
+    units_q: Query<(Entity, &Transform, &MovementPath, &PathGoal)>,
+    mut path_requests: EventWriter<PathRequest>,
+) {
+    let nav_mesh_lock = nav_mesh.get();
+    let Ok(nav_mesh) = nav_mesh_lock.try_read() else {
+        return;
+    };
+    let changed: HashSet<UVec2> = nav_mesh.tile_generations.iter()
+        .filter(|(tile, generation)| known_generations.get(*tile) != Some(*generation))
+        .map(|(tile, _)| *tile)
+        .collect();
+    if changed.is_empty() {
+        return;
+    }
+    known_generations.extend(changed.iter().map(|tile| (*tile, nav_mesh.tile_generations[tile])));
+
+    for (entity, transform, path, goal) in units_q.iter() {
+        if path.is_empty() {
+            continue;
+        }
+        let tiles = path_tiles(&nav_mesh_settings, transform.translation, path.waypoints());
+        if tiles.is_disjoint(&changed) {
+            continue;
+        }
+        info!("Nav mesh changed under {:?}'s path, repathing", entity);
+        path_requests.send(PathRequest {
+            entity,
+            goal: goal.goal,
+            search_radius: goal.search_radius,
+        });
+    }
+}
 
-impl From<ListenerInput<Pointer<Down>>> for MoveEvent {
-    fn from(event: ListenerInput<Pointer<Down>>) -> Self {
-        MoveEvent(event.hit.position)
+// Stuck units trying a repath get a fresh path to the same goal from where they ended up.
+fn repath_stuck_units(
+    mut stuck_events: EventReader<UnitStuck>,
+    units_q: Query<(&MovementPath, Option<&PathGoal>)>,
+    mut path_requests: EventWriter<PathRequest>,
+) {
+    for event in stuck_events.iter() {
+        if event.recovery != StuckRecovery::Repath {
+            continue;
+        }
+        let Ok((path, goal)) = units_q.get(event.entity) else {
+            continue;
+        };
+        let request = match (goal, path.goal()) {
+            (Some(goal), _) => PathRequest {
+                entity: event.entity,
+                goal: goal.goal,
+                search_radius: goal.search_radius,
+            },
+            (None, Some(goal)) => PathRequest::new(event.entity, goal),
+            (None, None) => continue,
+        };
+        path_requests.send(request);
     }
 }
 
@@ -135,10 +389,11 @@ async fn async_path_find(
     start_pos: Vec3,
     end_pos: Vec3,
     position_search_radius: Option<f32>,
-) -> Option<Vec<Vec3>> {
+) -> PathOutcome {
     // Get the underlying nav_mesh.
     let Ok(nav_mesh) = nav_mesh_lock.read() else {
-        return None;
+        error!("Nav mesh lock is poisoned");
+        return PathOutcome::NoPath;
     };
     // Run pathfinding to get a path.
     match find_path(
@@ -149,13 +404,16 @@ async fn async_path_find(
         position_search_radius,
         Some(&[1.0, 0.5]),
     ) {
+        Ok(path) if path.len() < 2 => PathOutcome::NoPath,
         Ok(path) => {
             info!("Found path (ASYNC): {:?}", path);
-            return Some(path);
+            PathOutcome::Found(path)
+        }
+        Err(error) => {
+            error!("Error with pathfinding: {:?}", error);
+            PathOutcome::Failed
         }
-        Err(error) => error!("Error with pathfinding: {:?}", error),
     }
-    None
 }
 
 fn toggle_nav_mesh_system(keys: Res<Input<KeyCode>>, mut show_navmesh: ResMut<DrawNavMesh>) {
@@ -169,10 +427,9 @@ mod pathfinding_test {
     use bevy::input::InputPlugin;
     use bevy::prelude::*;
     use bevy_xpbd_3d::plugins::PhysicsPlugins;
-    use crate::pathfinding::PathfindingPlugin;
+    use crate::pathfinding::{path_tiles, ActivePathRequest, AsyncPathfindingTasks, PathfindingPlugin, PathRequest, PathRequestId, PathResult};
 
-    #[test]
-    fn it_can_find_path_to_itself() {
+    fn setup() -> App {
         let mut app = App::new();
         app.add_plugins((
             MinimalPlugins,
@@ -181,6 +438,70 @@ mod pathfinding_test {
             PhysicsPlugins::default(),
             PathfindingPlugin::default()
         ));
+        app
+    }
+
+    #[test]
+    fn it_can_find_path_to_itself() {
+        let mut app = setup();
+        app.update();
+    }
+
+    #[test]
+    fn it_supersedes_previous_request_for_the_same_entity() {
+        let mut app = setup();
+        let entity = app.world.spawn(TransformBundle::default()).id();
+        app.world.send_event(PathRequest::new(entity, Vec3::X));
+        app.world.send_event(PathRequest::new(entity, Vec3::Z));
+        app.update();
+        assert!(app.world.resource::<AsyncPathfindingTasks>().len() <= 1);
+        assert_eq!(app.world.get::<ActivePathRequest>(entity), Some(&ActivePathRequest(PathRequestId(1))));
+    }
+
+    #[test]
+    fn it_drops_requests_of_despawned_entities() {
+        let mut app = setup();
+        let entity = app.world.spawn(TransformBundle::default()).id();
+        app.world.send_event(PathRequest::new(entity, Vec3::X));
         app.update();
+        app.world.despawn(entity);
+        app.update();
+        assert!(!app.world.resource::<AsyncPathfindingTasks>().is_pending(entity));
+    }
+
+    #[test]
+    fn it_reports_failure_and_stops_polling_when_there_is_no_nav_mesh() {
+        let mut app = setup();
+        let entity = app.world.spawn(TransformBundle::default()).id();
+        app.world.send_event(PathRequest::new(entity, Vec3::new(10., 0., 10.)));
+        let mut reader = app.world.resource::<Events<PathResult>>().get_reader();
+        let mut failures = 0;
+        for _ in 0..200 {
+            app.update();
+            let events = app.world.resource::<Events<PathResult>>();
+            failures += reader.iter(events).filter(|result| !result.is_found()).count();
+            if failures == 2 && app.world.resource::<AsyncPathfindingTasks>().is_empty() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        // The original request and its nearest-reachable-point fallback both fail.
+        assert_eq!(failures, 2);
+        assert!(!app.world.resource::<AsyncPathfindingTasks>().is_pending(entity));
+    }
+
+    #[test]
+    fn it_lists_every_tile_a_path_crosses() {
+        let settings = PathfindingPlugin::default().config;
+        let tile_size = settings.cell_width * settings.tile_width as f32;
+        let tiles = path_tiles(&settings, Vec3::new(1., 0., 1.), &[Vec3::new(1. + tile_size * 2., 0., 1.)]);
+        assert_eq!(tiles.len(), 3);
+    }
+
+    #[test]
+    fn it_stays_in_one_tile_for_short_paths() {
+        let settings = PathfindingPlugin::default().config;
+        let tiles = path_tiles(&settings, Vec3::new(1., 0., 1.), &[Vec3::new(2., 0., 1.), Vec3::new(2., 0., 2.)]);
+        assert_eq!(tiles.len(), 1);
     }
 }
@@ -0,0 +1,208 @@
+use bevy::prelude::*;
+use crate::avoidance::Avoidance;
+use crate::building::{BuildingKind, Footprint};
+use crate::game_state::{match_running, AppState, MatchEntity};
+use crate::input::{ActionState, CursorPosition, InputAction};
+use crate::selection::selection_input_system;
+use crate::map::MapSize;
+use crate::player::{LocalPlayer, Owner, Player};
+use crate::resources::Wallet;
+use crate::world::{spawn_building, BuildingAssets};
+
+pub struct PlacementPlugin;
+
+impl Plugin for PlacementPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<Placement>()
+            .register_type::<Footprint>()
+            .add_systems(
+                Update,
+                (placement_input_system, update_ghost_system, confirm_placement_system)
+                    .chain()
+                    .after(selection_input_system)
+                    .run_if(match_running))
+            .add_systems(OnExit(AppState::GameOver), reset_placement);
+    }
+}
+
+/// Building being placed, if any, and its ghost preview.
+#[derive(Resource, Default)]
+pub struct Placement {
+    building: Option<BuildingKind>,
+    ghost: Option<Entity>,
+    valid: bool,
+}
+
+impl Placement {
+    pub fn building(&self) -> Option<BuildingKind> {
+        self.building
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.building.is_some()
+    }
+}
+
+/// Run condition for systems that shouldn't react to clicks meant for placing a building.
+pub fn placing_building(placement: Option<Res<Placement>>) -> bool {
+    placement.is_some_and(|placement| placement.is_active())
+}
+
+/// Whether `kind` fits at `position`: on a ground of `map_size`, clear of other footprints and of units.
+pub fn can_place<'a>(
+    kind: BuildingKind,
+    position: Vec3,
+    map_size: f32,
+    mut obstacles: impl Iterator<Item = (Vec3, &'a Footprint)>,
+    mut units: impl Iterator<Item = (Vec3, f32)>,
+) -> bool {
+    let rect = kind.footprint().rect(position);
+    let ground = Rect::from_center_half_size(Vec2::ZERO, Vec2::splat(map_size / 2.));
+    if ground.intersect(rect) != rect {
+        return false;
+    }
+    !obstacles.any(|(center, footprint)| !footprint.rect(center).intersect(rect).is_empty())
+        && !units.any(|(center, radius)| rect.inset(radius).contains(Vec2::new(center.x, center.z)))
+}
+
+// Placement hotkeys start placing a building with a ghost under the cursor, cancel drops it.
+fn placement_input_system(
+    mut commands: Commands,
+    actions: Res<ActionState>,
+    assets: Res<BuildingAssets>,
+    mut placement: ResMut<Placement>,
+) {
+    if actions.just_pressed(InputAction::Cancel) {
+        end_placement(&mut commands, &mut placement);
+        return;
+    }
+    let Some(kind) = BuildingKind::ALL.into_iter()
+        .enumerate()
+        .find(|(index, _)| actions.just_pressed(InputAction::PlaceBuilding(*index)))
+        .map(|(_, kind)| kind) else {
+        return;
+    };
+    end_placement(&mut commands, &mut placement);
+    let (mesh, _) = assets.get(kind);
+    placement.building = Some(kind);
+    placement.valid = false;
+    placement.ghost = Some(commands.spawn((
+        PbrBundle {
+            mesh,
+            material: assets.ghost_invalid.clone(),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        MatchEntity,
+    )).id());
+}
+
+fn end_placement(commands: &mut Commands, placement: &mut Placement) {
+    if let Some(ghost) = placement.ghost.take() {
+        commands.entity(ghost).despawn();
+    }
+    placement.building = None;
+    placement.valid = false;
+}
+
+// The ghost is despawned with the rest of the match.
+fn reset_placement(mut placement: ResMut<Placement>) {
+    *placement = Placement::default();
+}
+
+fn update_ghost_system(
+    cursor: Res<CursorPosition>,
+    assets: Res<BuildingAssets>,
+    map_size: Option<Res<MapSize>>,
+    mut placement: ResMut<Placement>,
+    obstacles_q: Query<(&Transform, &Footprint)>,
+    units_q: Query<(&Transform, &Avoidance)>,
+    mut ghost_q: Query<(&mut Transform, &mut Handle<StandardMaterial>, &mut Visibility), (Without<Footprint>, Without<Avoidance>)>,
+) {
+    let (Some(kind), Some(ghost)) = (placement.building, placement.ghost) else {
+        return;
+    };
+    let Ok((mut transform, mut material, mut visibility)) = ghost_q.get_mut(ghost) else {
+        return;
+    };
+    let (Some(ground), Some(map_size)) = (cursor.ground, map_size) else {
+        *visibility = Visibility::Hidden;
+        placement.valid = false;
+        return;
+    };
+    transform.translation = Vec3::new(ground.x, kind.size().y / 2., ground.z);
+    *visibility = Visibility::Visible;
+    placement.valid = can_place(
+        kind,
+        ground,
+        map_size.0,
+        obstacles_q.iter().map(|(transform, footprint)| (transform.translation, footprint)),
+        units_q.iter().map(|(transform, avoidance)| (transform.translation, avoidance.radius)),
+    );
+    *material = if placement.valid { assets.ghost_valid.clone() } else { assets.ghost_invalid.clone() };
+}
+
+// Select places the building if it fits and the player can pay; holding queue keeps placing.
+fn confirm_placement_system(
+    mut commands: Commands,
+    actions: Res<ActionState>,
+    cursor: Res<CursorPosition>,
+    assets: Res<BuildingAssets>,
+    mut placement: ResMut<Placement>,
+    local: Res<LocalPlayer>,
+    mut wallet_q: Query<(&Player, &mut Wallet)>,
+) {
+    if !actions.just_pressed(InputAction::Select) {
+        return;
+    }
+    let (Some(kind), Some(position)) = (placement.building, cursor.ground) else {
+        return;
+    };
+    if !placement.valid {
+        warn!("Can't place {} here", kind.name());
+        return;
+    }
+    let Some((_, mut wallet)) = wallet_q.iter_mut().find(|(player, _)| player.id == local.0) else {
+        return;
+    };
+    if let Err(error) = wallet.try_spend(&kind.cost()) {
+        warn!("Not enough {} to place {}", error.kind.name().to_lowercase(), kind.name());
+        return;
+    }
+    spawn_building(&mut commands, &assets, kind, position, Owner(local.0));
+    info!("Placed {}", kind.name());
+
+    if !actions.pressed(InputAction::Queue) {
+        end_placement(&mut commands, &mut placement);
+    }
+}
+
+#[cfg(test)]
+mod placement_test {
+    use bevy::prelude::*;
+    use crate::building::{BuildingKind, Footprint};
+    use crate::placement::can_place;
+
+    #[test]
+    fn it_can_place_on_empty_ground() {
+        assert!(can_place(BuildingKind::Barracks, Vec3::ZERO, 75., std::iter::empty(), std::iter::empty()));
+    }
+
+    #[test]
+    fn it_cant_place_overlapping_another_footprint() {
+        let mine = Footprint::new(1., 1.);
+        assert!(!can_place(BuildingKind::Barracks, Vec3::ZERO, 75., std::iter::once((Vec3::new(2.5, 0., 0.), &mine)), std::iter::empty()));
+        assert!(can_place(BuildingKind::Barracks, Vec3::ZERO, 75., std::iter::once((Vec3::new(3.5, 0., 0.), &mine)), std::iter::empty()));
+    }
+
+    #[test]
+    fn it_cant_place_on_units() {
+        assert!(!can_place(BuildingKind::Barracks, Vec3::ZERO, 75., std::iter::empty(), std::iter::once((Vec3::new(2.2, 0.8, 0.), 0.5))));
+    }
+
+    #[test]
+    fn it_cant_place_off_the_ground_plane() {
+        assert!(!can_place(BuildingKind::Barracks, Vec3::new(37., 0., 0.), 75., std::iter::empty(), std::iter::empty()));
+    }
+}
@@ -0,0 +1,105 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Who owns what: players, their teams, and which player is the one playing on this machine.
+pub struct PlayerPlugin;
+
+impl Plugin for PlayerPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .register_type::<Player>()
+            .register_type::<Owner>()
+            .init_resource::<LocalPlayer>();
+    }
+}
+
+/// A player's index among the map's players.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Reflect, Serialize, Deserialize)]
+pub struct PlayerId(pub u8);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Reflect, Serialize, Deserialize)]
+pub struct Team(pub u8);
+
+/// A player in the match. The same entity holds their wallet and supply.
+#[derive(Component, Clone, Copy, Debug, Reflect)]
+pub struct Player {
+    pub id: PlayerId,
+    pub team: Team,
+}
+
+/// Player a unit or building belongs to.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Reflect)]
+pub struct Owner(pub PlayerId);
+
+/// How the local player stands towards the owner of a unit or building.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Relation {
+    Own,
+    Ally,
+    Enemy,
+}
+
+/// The player on this machine: only their units can be selected and ordered, and the UI shows their wallet.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LocalPlayer(pub PlayerId);
+
+impl LocalPlayer {
+    /// Whether the local player can select an entity: their own, or one nobody owns.
+    pub fn controls(&self, owner: Option<&Owner>) -> bool {
+        owner.map_or(true, |owner| owner.0 == self.0)
+    }
+
+    pub fn owns(&self, owner: &Owner) -> bool {
+        owner.0 == self.0
+    }
+
+    /// Allies are the other players on the local player's team. Unowned entities count as their own, as in `controls`.
+    pub fn relation<'a>(&self, owner: Option<&Owner>, players: impl IntoIterator<Item = &'a Player>) -> Relation {
+        let Some(owner) = owner.filter(|owner| !self.owns(owner)) else {
+            return Relation::Own;
+        };
+        let mut local_team = None;
+        let mut owner_team = None;
+        for player in players {
+            if player.id == self.0 {
+                local_team = Some(player.team);
+            }
+            if player.id == owner.0 {
+                owner_team = Some(player.team);
+            }
+        }
+        match (local_team, owner_team) {
+            (Some(local), Some(other)) if local == other => Relation::Ally,
+            _ => Relation::Enemy,
+        }
+    }
+}
+
+#[cfg(test)]
+mod player_test {
+    use crate::player::{LocalPlayer, Owner, Player, PlayerId, Relation, Team};
+
+    #[test]
+    fn it_controls_its_own_and_unowned_entities_only() {
+        let local = LocalPlayer(PlayerId(1));
+        assert!(local.controls(Some(&Owner(PlayerId(1)))));
+        assert!(local.controls(None));
+        assert!(!local.controls(Some(&Owner(PlayerId(0)))));
+    }
+
+    #[test]
+    fn it_tells_allies_from_enemies_by_team() {
+        let local = LocalPlayer(PlayerId(0));
+        let players = [
+            Player { id: PlayerId(0), team: Team(0) },
+            Player { id: PlayerId(1), team: Team(0) },
+            Player { id: PlayerId(2), team: Team(1) },
+        ];
+        assert_eq!(local.relation(Some(&Owner(PlayerId(0))), &players), Relation::Own);
+        assert_eq!(local.relation(None, &players), Relation::Own);
+        assert_eq!(local.relation(Some(&Owner(PlayerId(1))), &players), Relation::Ally);
+        assert_eq!(local.relation(Some(&Owner(PlayerId(2))), &players), Relation::Enemy);
+        // Without a team to compare, other players are enemies.
+        assert_eq!(local.relation(Some(&Owner(PlayerId(3))), &players), Relation::Enemy);
+    }
+}
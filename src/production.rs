@@ -0,0 +1,399 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+use bevy::prelude::*;
+use crate::game_state::match_running;
+use crate::resources::{Cost, NotEnoughResourcesError, Wallet};
+use crate::input::{ActionState, InputAction};
+use crate::orders::{MoveEvent, OrderQueue};
+use crate::player::{Owner, Player};
+use crate::selection::Selected;
+use crate::supply::{NotEnoughSupplyError, Supply};
+use crate::unit::{find_unit_def, spawn_unit, UnitDef};
+
+/// Distance from a building's center at which trained units spawn.
+const SPAWN_DISTANCE: f32 = 3.;
+
+pub struct ProductionPlugin;
+
+impl Plugin for ProductionPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<TrainUnit>()
+            .add_event::<CancelTraining>()
+            .register_type::<ProductionQueue>()
+            .register_type::<RallyPoint>()
+            .add_systems(
+                Update,
+                (
+                    (train_action_system, queue_training_system, cancel_training_system, production_system).chain(),
+                    rally_point_system,
+                ).run_if(match_running));
+    }
+}
+
+/// Request to queue a unit at a production building.
+#[derive(Event, Clone, Debug)]
+pub struct TrainUnit {
+    pub building: Entity,
+    /// Name of the unit type to train.
+    pub unit: String,
+}
+
+/// Request to cancel the unit in `slot` of a building's queue, refunding its cost.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct CancelTraining {
+    pub building: Entity,
+    pub slot: usize,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TrainError {
+    NotTrainable,
+    /// The unit type hasn't loaded (yet).
+    UnknownUnit,
+    QueueFull,
+    NotEnoughResources(NotEnoughResourcesError),
+    NotEnoughSupply(NotEnoughSupplyError),
+}
+
+impl From<NotEnoughResourcesError> for TrainError {
+    fn from(error: NotEnoughResourcesError) -> Self {
+        TrainError::NotEnoughResources(error)
+    }
+}
+
+impl From<NotEnoughSupplyError> for TrainError {
+    fn from(error: NotEnoughSupplyError) -> Self {
+        TrainError::NotEnoughSupply(error)
+    }
+}
+
+/// A queued unit, with the price paid for it so cancelling refunds exactly that.
+#[derive(Clone, Debug, Reflect)]
+pub struct TrainingJob {
+    pub name: String,
+    pub unit_def: Handle<UnitDef>,
+    pub cost: Cost,
+    pub supply: u32,
+    pub progress: Timer,
+}
+
+/// Units a building is training, oldest first. Only the first one makes progress.
+#[derive(Component, Reflect)]
+pub struct ProductionQueue {
+    pub max_slots: usize,
+    trainable: Vec<String>,
+    jobs: VecDeque<TrainingJob>,
+}
+
+impl ProductionQueue {
+    pub fn new(max_slots: usize, trainable: Vec<String>) -> Self {
+        Self {
+            max_slots,
+            trainable,
+            jobs: VecDeque::new(),
+        }
+    }
+
+    /// Names of the unit types the building trains.
+    pub fn trainable(&self) -> &[String] {
+        &self.trainable
+    }
+
+    pub fn jobs(&self) -> impl Iterator<Item = &TrainingJob> {
+        self.jobs.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.jobs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.jobs.len() >= self.max_slots
+    }
+
+    /// Fraction of the current unit's build time done.
+    pub fn progress(&self) -> Option<f32> {
+        self.jobs.front().map(|job| job.progress.percent())
+    }
+
+    /// Supply taken by the queued units, held until they spawn or are cancelled.
+    pub fn reserved_supply(&self) -> u32 {
+        self.jobs.iter().map(|job| job.supply).sum()
+    }
+
+    /// Queues a unit of type `unit_def`, paying its cost and reserving its supply up front.
+    pub fn enqueue(
+        &mut self,
+        handle: Handle<UnitDef>,
+        unit_def: &UnitDef,
+        wallet: &mut Wallet,
+        supply: &mut Supply,
+    ) -> Result<(), TrainError> {
+        if !self.trainable.contains(&unit_def.name) {
+            return Err(TrainError::NotTrainable);
+        }
+        if self.is_full() {
+            return Err(TrainError::QueueFull);
+        }
+        supply.can_add_amount(unit_def.supply)?;
+        wallet.try_spend(&unit_def.cost)?;
+        supply.add_amount(unit_def.supply)?;
+        self.jobs.push_back(TrainingJob {
+            name: unit_def.name.clone(),
+            unit_def: handle,
+            cost: unit_def.cost,
+            supply: unit_def.supply,
+            progress: Timer::from_seconds(unit_def.build_time, TimerMode::Once),
+        });
+        Ok(())
+    }
+
+    /// Removes the unit in `slot`, refunding its cost and releasing its supply.
+    pub fn cancel(&mut self, slot: usize, wallet: &mut Wallet, supply: &mut Supply) -> Option<TrainingJob> {
+        let job = self.jobs.remove(slot)?;
+        wallet.refund(&job.cost);
+        supply.remove_amount(job.supply);
+        Some(job)
+    }
+
+    /// Advances the current unit, returning it once its build time is done.
+    pub fn tick(&mut self, delta: Duration) -> Option<TrainingJob> {
+        let job = self.jobs.front_mut()?;
+        if !job.progress.tick(delta).finished() {
+            return None;
+        }
+        self.jobs.pop_front()
+    }
+}
+
+/// Where a building sends the units it trains.
+#[derive(Component, Default, Reflect)]
+pub struct RallyPoint(pub Option<Vec3>);
+
+// Train hotkeys pick from the selected buildings' unit lists, cancel drops the last queued unit.
+fn train_action_system(
+    actions: Res<ActionState>,
+    buildings_q: Query<(Entity, &ProductionQueue), With<Selected>>,
+    mut train_events: EventWriter<TrainUnit>,
+    mut cancel_events: EventWriter<CancelTraining>,
+) {
+    for (building, queue) in buildings_q.iter() {
+        for (index, unit) in queue.trainable().iter().enumerate() {
+            if actions.just_pressed(InputAction::Train(index)) {
+                train_events.send(TrainUnit { building, unit: unit.clone() });
+            }
+        }
+        if actions.just_pressed(InputAction::CancelTraining) && !queue.is_empty() {
+            cancel_events.send(CancelTraining { building, slot: queue.len() - 1 });
+        }
+    }
+}
+
+// Units are paid for from the wallet and supply of the building's owner.
+fn queue_training_system(
+    mut train_events: EventReader<TrainUnit>,
+    unit_defs: Res<Assets<UnitDef>>,
+    mut wallet_q: Query<(&Player, &mut Wallet, &mut Supply)>,
+    mut buildings_q: Query<(&mut ProductionQueue, &Owner)>,
+) {
+    for event in train_events.iter() {
+        let Ok((mut queue, owner)) = buildings_q.get_mut(event.building) else {
+            continue;
+        };
+        let Some((_, mut wallet, mut supply)) = wallet_q.iter_mut().find(|(player, ..)| player.id == owner.0) else {
+            continue;
+        };
+        let result = match find_unit_def(&unit_defs, &event.unit) {
+            Some((handle, unit_def)) => queue.enqueue(handle, unit_def, &mut wallet, &mut supply),
+            None => Err(TrainError::UnknownUnit),
+        };
+        match result {
+            Ok(()) => info!("Training {} at {:?}", event.unit, event.building),
+            Err(error) => warn!("Can't train {}: {:?}", event.unit, error),
+        }
+    }
+}
+
+fn cancel_training_system(
+    mut cancel_events: EventReader<CancelTraining>,
+    mut wallet_q: Query<(&Player, &mut Wallet, &mut Supply)>,
+    mut buildings_q: Query<(&mut ProductionQueue, &Owner)>,
+) {
+    for event in cancel_events.iter() {
+        let Ok((mut queue, owner)) = buildings_q.get_mut(event.building) else {
+            continue;
+        };
+        if let Some((_, mut wallet, mut supply)) = wallet_q.iter_mut().find(|(player, ..)| player.id == owner.0) {
+            queue.cancel(event.slot, &mut wallet, &mut supply);
+        }
+    }
+}
+
+// Spawn finished units next to their building, on the side facing the rally point.
+fn production_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    unit_defs: Res<Assets<UnitDef>>,
+    mut buildings_q: Query<(&Transform, &mut ProductionQueue, Option<&RallyPoint>, &Owner)>,
+) {
+    for (transform, mut queue, rally_point, owner) in buildings_q.iter_mut() {
+        let Some(job) = queue.tick(time.delta()) else {
+            continue;
+        };
+        let Some(unit_def) = unit_defs.get(&job.unit_def) else {
+            warn!("Unit type {} was unloaded before it finished training", job.name);
+            continue;
+        };
+        let rally_point = rally_point.and_then(|rally_point| rally_point.0);
+        let direction = rally_point
+            .map(|rally_point| Vec3::new(rally_point.x - transform.translation.x, 0., rally_point.z - transform.translation.z))
+            .and_then(|direction| direction.try_normalize())
+            .unwrap_or(Vec3::NEG_Z);
+        let unit = spawn_unit(&mut commands, unit_def, transform.translation + direction * SPAWN_DISTANCE, *owner);
+        if let Some(rally_point) = rally_point {
+            let mut order_queue = OrderQueue::default();
+            order_queue.push(rally_point);
+            commands.entity(unit).insert(order_queue);
+        }
+        info!("{} trained", job.name);
+    }
+}
+
+// A move order given while a building is selected moves its rally point.
+fn rally_point_system(
+    mut move_events: EventReader<MoveEvent>,
+    mut buildings_q: Query<&mut RallyPoint, With<Selected>>,
+) {
+    for event in move_events.iter() {
+        for mut rally_point in buildings_q.iter_mut() {
+            rally_point.0 = Some(event.target);
+        }
+    }
+}
+
+#[cfg(test)]
+mod production_test {
+    use std::time::Duration;
+    use bevy::prelude::*;
+    use bevy::time::TimeUpdateStrategy;
+    use crate::game_state::{AppState, MatchState};
+    use crate::resources::{Cost, NotEnoughResourcesError, ResourceKind, Wallet};
+    use crate::input::ActionState;
+    use crate::orders::{MoveEvent, OrderQueue};
+    use crate::player::{Owner, Player, PlayerId, Team};
+    use crate::production::{ProductionPlugin, ProductionQueue, RallyPoint, TrainError, TrainUnit};
+    use crate::supply::{Supply, SupplyCost};
+    use crate::unit::UnitDef;
+
+    fn wallet(gold: u32, capacity: u32) -> (Wallet, Supply) {
+        let mut supply = Supply::default();
+        supply.add_capacity(capacity);
+        (Wallet::new(Cost::gold(gold)), supply)
+    }
+
+    fn worker() -> UnitDef {
+        UnitDef::from_ron(include_str!("../assets/units/worker.unit.ron")).unwrap()
+    }
+
+    fn queue(max_slots: usize) -> ProductionQueue {
+        ProductionQueue::new(max_slots, vec![worker().name])
+    }
+
+    #[test]
+    fn it_pays_gold_and_supply_when_queueing() {
+        let (mut gold, mut supply) = wallet(100, 10);
+        let mut queue = queue(5);
+        queue.enqueue(Handle::default(), &worker(), &mut gold, &mut supply).unwrap();
+        assert_eq!(gold.balance(ResourceKind::Gold), 100 - worker().cost.get(ResourceKind::Gold));
+        assert_eq!(supply.amount(), worker().supply);
+        assert_eq!(queue.len(), 1);
+    }
+
+    #[test]
+    fn it_cant_queue_more_than_max_slots() {
+        let (mut gold, mut supply) = wallet(1000, 10);
+        let mut queue = queue(1);
+        queue.enqueue(Handle::default(), &worker(), &mut gold, &mut supply).unwrap();
+        assert_eq!(queue.enqueue(Handle::default(), &worker(), &mut gold, &mut supply), Err(TrainError::QueueFull));
+    }
+
+    #[test]
+    fn it_cant_queue_units_the_building_doesnt_train() {
+        let (mut gold, mut supply) = wallet(1000, 10);
+        let mut queue = ProductionQueue::new(5, vec!["Soldier".to_string()]);
+        assert_eq!(queue.enqueue(Handle::default(), &worker(), &mut gold, &mut supply), Err(TrainError::NotTrainable));
+    }
+
+    #[test]
+    fn it_doesnt_take_gold_without_enough_supply() {
+        let (mut gold, mut supply) = wallet(100, 0);
+        let mut queue = queue(5);
+        assert!(matches!(
+            queue.enqueue(Handle::default(), &worker(), &mut gold, &mut supply),
+            Err(TrainError::NotEnoughSupply(_))));
+        assert_eq!(gold.balance(ResourceKind::Gold), 100);
+    }
+
+    #[test]
+    fn it_doesnt_reserve_supply_without_enough_gold() {
+        let (mut gold, mut supply) = wallet(0, 10);
+        let mut queue = queue(5);
+        assert_eq!(
+            queue.enqueue(Handle::default(), &worker(), &mut gold, &mut supply),
+            Err(TrainError::NotEnoughResources(NotEnoughResourcesError {
+                kind: ResourceKind::Gold,
+                missing: worker().cost.get(ResourceKind::Gold),
+            })));
+        assert_eq!(supply.amount(), 0);
+    }
+
+    #[test]
+    fn it_refunds_cancelled_units() {
+        let (mut gold, mut supply) = wallet(100, 10);
+        let mut queue = queue(5);
+        queue.enqueue(Handle::default(), &worker(), &mut gold, &mut supply).unwrap();
+        assert_eq!(queue.cancel(0, &mut gold, &mut supply).map(|job| job.name), Some(worker().name));
+        assert_eq!(gold.balance(ResourceKind::Gold), 100);
+        assert_eq!(supply.amount(), 0);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn it_spawns_trained_unit_with_rally_order() {
+        let mut app = App::new();
+        app
+            .add_state::<AppState>()
+            .add_state::<MatchState>()
+            .insert_resource(NextState(Some(AppState::InGame)))
+            .add_plugins((MinimalPlugins, AssetPlugin::default(), ProductionPlugin))
+            .add_asset::<UnitDef>()
+            .add_event::<MoveEvent>()
+            .init_resource::<ActionState>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs(1)));
+        app.world.resource_mut::<Assets<UnitDef>>().add(worker());
+        app.world.spawn((Player { id: PlayerId(1), team: Team(1) }, wallet(100, 10)));
+        let rally_point = Vec3::new(10., 0., 0.);
+        let building = app.world.spawn((
+            TransformBundle::default(),
+            queue(5),
+            RallyPoint(Some(rally_point)),
+            Owner(PlayerId(1)),
+        )).id();
+        app.world.send_event(TrainUnit { building, unit: worker().name });
+
+        for _ in 0..(worker().build_time as usize + 2) {
+            app.update();
+        }
+
+        assert!(app.world.get::<ProductionQueue>(building).unwrap().is_empty());
+        let (order_queue, cost, owner) = app.world.query::<(&OrderQueue, &SupplyCost, &Owner)>().single(&app.world);
+        assert_eq!(order_queue.last(), Some(rally_point));
+        assert_eq!(cost.0, worker().supply);
+        assert_eq!(*owner, Owner(PlayerId(1)));
+    }
+}
@@ -0,0 +1,272 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::time::Duration;
+use bevy::app::{App, Plugin};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use crate::game_state::{match_running, AppState};
+use crate::player::Player;
+use crate::supply::Supply;
+
+/// Seconds of earnings averaged into the income rate.
+const INCOME_WINDOW: usize = 10;
+const RESOURCE_KINDS: usize = 3;
+
+pub struct ResourcesPlugin;
+
+impl Plugin for ResourcesPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(OnEnter(AppState::InGame), setup_resource)
+            .add_systems(Update, track_income_system.run_if(match_running));
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Reflect, Serialize, Deserialize)]
+pub enum ResourceKind {
+    Gold,
+    Fuel,
+    Crystals,
+}
+
+impl ResourceKind {
+    /// Order the resources are shown in.
+    pub const ALL: [ResourceKind; RESOURCE_KINDS] = [ResourceKind::Gold, ResourceKind::Fuel, ResourceKind::Crystals];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ResourceKind::Gold => "Gold",
+            ResourceKind::Fuel => "Fuel",
+            ResourceKind::Crystals => "Crystals",
+        }
+    }
+
+    fn index(&self) -> usize {
+        *self as usize
+    }
+}
+
+/// An amount of each resource kind, such as a price. Written in RON as a map, e.g. `{Gold: 50, Fuel: 10}`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect, Deserialize)]
+#[serde(from = "HashMap<ResourceKind, u32>")]
+pub struct Cost([u32; RESOURCE_KINDS]);
+
+impl Cost {
+    pub fn new(amounts: impl IntoIterator<Item = (ResourceKind, u32)>) -> Self {
+        let mut cost = Self::default();
+        for (kind, amount) in amounts {
+            cost.0[kind.index()] += amount;
+        }
+        cost
+    }
+
+    pub fn gold(amount: u32) -> Self {
+        Self::new([(ResourceKind::Gold, amount)])
+    }
+
+    pub fn get(&self, kind: ResourceKind) -> u32 {
+        self.0[kind.index()]
+    }
+
+    /// The kinds with a non-zero amount.
+    pub fn iter(&self) -> impl Iterator<Item = (ResourceKind, u32)> + '_ {
+        ResourceKind::ALL.into_iter()
+            .map(|kind| (kind, self.get(kind)))
+            .filter(|(_, amount)| *amount > 0)
+    }
+}
+
+impl From<HashMap<ResourceKind, u32>> for Cost {
+    fn from(amounts: HashMap<ResourceKind, u32>) -> Self {
+        Self::new(amounts)
+    }
+}
+
+impl fmt::Display for Cost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let amounts: Vec<String> = self.iter()
+            .map(|(kind, amount)| format!("{amount} {}", kind.name().to_lowercase()))
+            .collect();
+        if amounts.is_empty() {
+            write!(f, "nothing")
+        } else {
+            write!(f, "{}", amounts.join(", "))
+        }
+    }
+}
+
+/// A player's stock of every resource kind, and what they earned over the last few seconds.
+#[derive(Component, Default, Debug)]
+pub struct Wallet {
+    balance: Cost,
+    earning: Cost,
+    /// Earnings of each of the last `INCOME_WINDOW` seconds, oldest first.
+    earned: VecDeque<Cost>,
+    elapsed: Duration,
+}
+
+impl Wallet {
+    pub fn new(balance: Cost) -> Self {
+        Self {
+            balance,
+            ..default()
+        }
+    }
+
+    pub fn balance(&self, kind: ResourceKind) -> u32 {
+        self.balance.get(kind)
+    }
+
+    /// Adds harvested resources, which count towards the income.
+    pub fn gain(&mut self, kind: ResourceKind, amount: u32) {
+        self.balance.0[kind.index()] += amount;
+        self.earning.0[kind.index()] += amount;
+    }
+
+    /// Gives back a cost paid earlier, without counting it as income.
+    pub fn refund(&mut self, cost: &Cost) {
+        for (kind, amount) in cost.iter() {
+            self.balance.0[kind.index()] += amount;
+        }
+    }
+
+    /// Pays `cost` if every resource in it is covered, and pays nothing otherwise.
+    pub fn try_spend(&mut self, cost: &Cost) -> Result<(), NotEnoughResourcesError> {
+        if let Some((kind, amount)) = cost.iter().find(|(kind, amount)| *amount > self.balance(*kind)) {
+            return Err(NotEnoughResourcesError { kind, missing: amount - self.balance(kind) });
+        }
+        for (kind, amount) in cost.iter() {
+            self.balance.0[kind.index()] -= amount;
+        }
+        Ok(())
+    }
+
+    /// Average earned per second over the last `INCOME_WINDOW` seconds.
+    pub fn income(&self, kind: ResourceKind) -> f32 {
+        if self.earned.is_empty() {
+            return 0.;
+        }
+        self.earned.iter().map(|earned| earned.get(kind)).sum::<u32>() as f32 / self.earned.len() as f32
+    }
+
+    /// Closes off every whole second that passed, dropping those older than the income window.
+    pub fn tick(&mut self, delta: Duration) {
+        self.elapsed += delta;
+        while self.elapsed >= Duration::from_secs(1) {
+            self.elapsed -= Duration::from_secs(1);
+            self.earned.push_back(std::mem::take(&mut self.earning));
+            if self.earned.len() > INCOME_WINDOW {
+                self.earned.pop_front();
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NotEnoughResourcesError {
+    pub kind: ResourceKind,
+    pub missing: u32,
+}
+
+// Each player's wallet: resource balances and supply used / capacity.
+fn setup_resource(mut commands: Commands, players_q: Query<Entity, With<Player>>) {
+    for player in players_q.iter() {
+        commands.entity(player).insert((Wallet::new(Cost::gold(50)), Supply::default()));
+    }
+}
+
+// Income follows game time, so it stands still while paused.
+fn track_income_system(time: Res<Time>, mut wallets_q: Query<&mut Wallet>) {
+    for mut wallet in wallets_q.iter_mut() {
+        wallet.tick(time.delta());
+    }
+}
+
+#[cfg(test)]
+mod resources_ui_test {
+    use std::time::Duration;
+    use bevy::core_pipeline::core_2d::Core2dPlugin;
+    use bevy::prelude::{App, NextState};
+    use crate::game_state::{AppState, MatchState};
+    use crate::player::{Player, PlayerId, Team};
+    use crate::resources::{Cost, NotEnoughResourcesError, ResourceKind, ResourcesPlugin, Wallet};
+
+    #[test]
+    fn it_adds_resources() {
+        let mut wallet = Wallet::default();
+        wallet.gain(ResourceKind::Gold, 5);
+        assert_eq!(wallet.balance(ResourceKind::Gold), 5);
+        wallet.gain(ResourceKind::Gold, 6);
+        wallet.gain(ResourceKind::Fuel, 2);
+        assert_eq!(wallet.balance(ResourceKind::Gold), 11);
+        assert_eq!(wallet.balance(ResourceKind::Fuel), 2);
+    }
+
+    #[test]
+    fn it_remains_the_same_when_spending_nothing() {
+        let mut wallet = Wallet::default();
+        wallet.try_spend(&Cost::default()).unwrap();
+        assert_eq!(wallet.balance(ResourceKind::Gold), 0);
+    }
+
+    #[test]
+    fn it_errors_if_not_enough_balance_when_spending() {
+        let mut wallet = Wallet::default();
+        let result = wallet.try_spend(&Cost::gold(5));
+        assert_eq!(result.unwrap_err(), NotEnoughResourcesError { kind: ResourceKind::Gold, missing: 5 });
+    }
+
+    #[test]
+    fn it_spends_every_resource_of_the_cost() {
+        let mut wallet = Wallet::new(Cost::new([(ResourceKind::Gold, 4), (ResourceKind::Crystals, 2)]));
+        wallet.try_spend(&Cost::new([(ResourceKind::Gold, 3), (ResourceKind::Crystals, 2)])).unwrap();
+        assert_eq!(wallet.balance(ResourceKind::Gold), 1);
+        assert_eq!(wallet.balance(ResourceKind::Crystals), 0);
+    }
+
+    #[test]
+    fn it_spends_nothing_if_any_resource_is_missing() {
+        let mut wallet = Wallet::new(Cost::new([(ResourceKind::Gold, 100), (ResourceKind::Fuel, 5)]));
+        let result = wallet.try_spend(&Cost::new([(ResourceKind::Gold, 50), (ResourceKind::Fuel, 8)]));
+        assert_eq!(result.unwrap_err(), NotEnoughResourcesError { kind: ResourceKind::Fuel, missing: 3 });
+        assert_eq!(wallet.balance(ResourceKind::Gold), 100);
+        assert_eq!(wallet.balance(ResourceKind::Fuel), 5);
+    }
+
+    #[test]
+    fn it_averages_income_per_second() {
+        let mut wallet = Wallet::default();
+        wallet.gain(ResourceKind::Gold, 10);
+        wallet.tick(Duration::from_millis(500));
+        assert_eq!(wallet.income(ResourceKind::Gold), 0.);
+        wallet.tick(Duration::from_millis(1500));
+        assert_eq!(wallet.income(ResourceKind::Gold), 5.);
+        // Refunds are given back, not earned.
+        wallet.refund(&Cost::gold(20));
+        wallet.tick(Duration::from_secs(20));
+        assert_eq!(wallet.income(ResourceKind::Gold), 0.);
+        assert_eq!(wallet.balance(ResourceKind::Gold), 30);
+    }
+
+    #[test]
+    fn it_reads_costs_as_a_map_of_resources() {
+        let cost: Cost = ron::from_str("{Gold: 50, Fuel: 10}").unwrap();
+        assert_eq!(cost, Cost::new([(ResourceKind::Gold, 50), (ResourceKind::Fuel, 10)]));
+        assert_eq!(cost.to_string(), "50 gold, 10 fuel");
+    }
+
+    #[test]
+    fn on_game_start_it_sets_gold_resource_to_50() {
+        let mut app = App::new();
+        app
+            .add_state::<AppState>()
+            .add_state::<MatchState>()
+            .insert_resource(NextState(Some(AppState::InGame)))
+            .add_plugins((Core2dPlugin, ResourcesPlugin));
+        app.world.spawn(Player { id: PlayerId(0), team: Team(0) });
+        app.update();
+        let wallet = app.world.query::<&Wallet>().single(&app.world);
+        assert_eq!(wallet.balance(ResourceKind::Gold), 50);
+        assert_eq!(wallet.balance(ResourceKind::Fuel), 0);
+    }
+}
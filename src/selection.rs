@@ -0,0 +1,324 @@
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+use bevy_mod_picking::prelude::*;
+use crate::camera::MainCamera;
+use crate::game_state::{match_running, AppState, MatchEntity};
+use crate::input::{ActionState, CursorPosition, InputAction, InputBindings};
+use crate::orders::targeting_order;
+use crate::placement::placing_building;
+use crate::player::{LocalPlayer, Owner};
+
+/// Minimum size in logical pixels before a drag counts as a box selection instead of a click.
+const DRAG_THRESHOLD: f32 = 4.;
+
+pub struct SelectionPlugin;
+
+impl Plugin for SelectionPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_event::<SelectEvent>()
+            .add_event::<SelectionChanged>()
+            .insert_resource(SelectionBox::default())
+            .add_systems(OnEnter(AppState::InGame), setup_selection_box_ui)
+            .add_systems(
+                Update,
+                (
+                    selection_input_system.run_if(not(placing_building)).run_if(not(targeting_order)),
+                    update_selection_box_ui,
+                    apply_selection_system,
+                ).chain().run_if(match_running));
+    }
+}
+
+/// Units that can be picked by clicking or box-dragging.
+#[derive(Component, Default)]
+pub struct Selectable;
+
+#[derive(Component)]
+pub struct Selected;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SelectionMode {
+    /// Drops the current selection and selects only the given entities.
+    Replace,
+    /// Adds the given entities to the current selection.
+    Add,
+    /// Deselects the given entities that are selected and selects the rest.
+    Toggle,
+}
+
+/// Request to change the selection, sent by the input systems or by anything else (UI, hotkeys).
+#[derive(Event)]
+pub struct SelectEvent {
+    pub entities: Vec<Entity>,
+    pub mode: SelectionMode,
+}
+
+/// Sent once per frame in which the set of `Selected` entities changed.
+#[derive(Event, Debug)]
+pub struct SelectionChanged {
+    pub selected: Vec<Entity>,
+}
+
+/// Screen-space rectangle being dragged while `InputAction::Select` is held.
+#[derive(Resource, Default)]
+pub struct SelectionBox {
+    start: Option<Vec2>,
+    end: Vec2,
+}
+
+impl SelectionBox {
+    pub fn rect(&self) -> Option<Rect> {
+        self.start.map(|start| Rect::from_corners(start, self.end))
+    }
+
+    pub fn is_drag(&self) -> bool {
+        self.rect()
+            .is_some_and(|rect| rect.width() >= DRAG_THRESHOLD || rect.height() >= DRAG_THRESHOLD)
+    }
+}
+
+#[derive(Component)]
+struct SelectionBoxNode;
+
+fn setup_selection_box_ui(mut commands: Commands) {
+    commands.spawn((
+        SelectionBoxNode,
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                border: UiRect::all(Val::Px(1.)),
+                ..default()
+            },
+            background_color: Color::rgba(0.4, 0.8, 0.4, 0.15).into(),
+            border_color: Color::rgb(0.4, 0.8, 0.4).into(),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        Pickable::IGNORE,
+        MatchEntity,
+    ));
+}
+
+pub(crate) fn selection_input_system(
+    actions: Res<ActionState>,
+    bindings: Res<InputBindings>,
+    cursor: Res<CursorPosition>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    selectable_q: Query<(Entity, &GlobalTransform), With<Selectable>>,
+    mut clicks: EventReader<Pointer<Click>>,
+    mut selection_box: ResMut<SelectionBox>,
+    mut select_events: EventWriter<SelectEvent>,
+) {
+    if let Some(screen) = cursor.screen {
+        // Clicks on UI panels such as the minimap neither start a box nor count as clicking empty ground.
+        if actions.just_pressed(InputAction::Select) && !cursor.over_ui {
+            selection_box.start = Some(screen);
+        }
+        selection_box.end = screen;
+    }
+    if !actions.just_released(InputAction::Select) || selection_box.start.is_none() {
+        clicks.clear();
+        return;
+    }
+
+    let shift = actions.pressed(InputAction::AddToSelection);
+    if selection_box.is_drag() {
+        clicks.clear();
+        let (Some(rect), Ok((camera, camera_transform))) = (selection_box.rect(), camera_q.get_single()) else {
+            selection_box.start = None;
+            return;
+        };
+        let entities = selectable_q.iter()
+            .filter(|(_, transform)| camera
+                .world_to_viewport(camera_transform, transform.translation())
+                .is_some_and(|position| rect.contains(position)))
+            .map(|(entity, _)| entity)
+            .collect();
+        select_events.send(SelectEvent {
+            entities,
+            mode: if shift { SelectionMode::Add } else { SelectionMode::Replace },
+        });
+    } else {
+        let select_buttons: Vec<PointerButton> = bindings.get(InputAction::Select)
+            .iter()
+            .filter_map(|binding| binding.pointer_button())
+            .collect();
+        let clicked = clicks.iter()
+            .filter(|click| select_buttons.contains(&click.button))
+            .find_map(|click| selectable_q.get(click.target).ok())
+            .map(|(entity, _)| entity);
+        select_events.send(SelectEvent {
+            entities: clicked.into_iter().collect(),
+            mode: if shift { SelectionMode::Toggle } else { SelectionMode::Replace },
+        });
+    }
+    selection_box.start = None;
+}
+
+fn update_selection_box_ui(
+    selection_box: Res<SelectionBox>,
+    mut node_q: Query<(&mut Style, &mut Visibility), With<SelectionBoxNode>>,
+) {
+    let Ok((mut style, mut visibility)) = node_q.get_single_mut() else {
+        return;
+    };
+    match selection_box.rect().filter(|_| selection_box.is_drag()) {
+        Some(rect) => {
+            style.left = Val::Px(rect.min.x);
+            style.top = Val::Px(rect.min.y);
+            style.width = Val::Px(rect.width());
+            style.height = Val::Px(rect.height());
+            *visibility = Visibility::Visible;
+        }
+        None => *visibility = Visibility::Hidden,
+    }
+}
+
+// Other players' units and buildings are left out, so only the local player's can be ordered around.
+fn apply_selection_system(
+    mut commands: Commands,
+    local: Res<LocalPlayer>,
+    mut select_events: EventReader<SelectEvent>,
+    selected_q: Query<Entity, With<Selected>>,
+    owners_q: Query<&Owner>,
+    mut changed_events: EventWriter<SelectionChanged>,
+) {
+    if select_events.is_empty() {
+        return;
+    }
+    let previous: HashSet<Entity> = selected_q.iter().collect();
+    let mut selection = previous.clone();
+    for event in select_events.iter() {
+        let entities: Vec<Entity> = event.entities.iter()
+            .copied()
+            .filter(|entity| local.controls(owners_q.get(*entity).ok()))
+            .collect();
+        apply_selection(&mut selection, &entities, event.mode);
+    }
+    if selection == previous {
+        return;
+    }
+    for entity in previous.difference(&selection) {
+        commands.entity(*entity).remove::<Selected>();
+    }
+    for entity in selection.difference(&previous) {
+        commands.entity(*entity).insert(Selected);
+    }
+    let mut selected: Vec<Entity> = selection.into_iter().collect();
+    selected.sort();
+    changed_events.send(SelectionChanged { selected });
+}
+
+pub fn apply_selection(selection: &mut HashSet<Entity>, entities: &[Entity], mode: SelectionMode) {
+    match mode {
+        SelectionMode::Replace => {
+            selection.clear();
+            selection.extend(entities.iter().copied());
+        }
+        SelectionMode::Add => selection.extend(entities.iter().copied()),
+        SelectionMode::Toggle => {
+            for entity in entities {
+                if !selection.remove(entity) {
+                    selection.insert(*entity);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod selection_test {
+    use bevy::prelude::*;
+    use crate::player::{LocalPlayer, Owner, PlayerId};
+    use crate::selection::{apply_selection_system, SelectEvent, Selected, SelectionChanged, SelectionMode};
+
+    fn setup() -> App {
+        let mut app = App::new();
+        app
+            .add_plugins(MinimalPlugins)
+            .init_resource::<LocalPlayer>()
+            .add_event::<SelectEvent>()
+            .add_event::<SelectionChanged>()
+            .add_systems(Update, apply_selection_system);
+        app
+    }
+
+    fn select(app: &mut App, entities: Vec<Entity>, mode: SelectionMode) {
+        app.world.send_event(SelectEvent { entities, mode });
+        app.update();
+    }
+
+    fn selected(app: &mut App) -> Vec<Entity> {
+        let mut selected: Vec<Entity> = app.world.query_filtered::<Entity, With<Selected>>()
+            .iter(&app.world)
+            .collect();
+        selected.sort();
+        selected
+    }
+
+    #[test]
+    fn it_replaces_selection() {
+        let mut app = setup();
+        let a = app.world.spawn(Selected).id();
+        let b = app.world.spawn_empty().id();
+        select(&mut app, vec![b], SelectionMode::Replace);
+        assert_eq!(selected(&mut app), vec![b]);
+        assert!(app.world.get::<Selected>(a).is_none());
+    }
+
+    #[test]
+    fn it_doesnt_select_other_players_units() {
+        let mut app = setup();
+        let own = app.world.spawn(Owner(PlayerId(0))).id();
+        let other = app.world.spawn(Owner(PlayerId(1))).id();
+        select(&mut app, vec![own, other], SelectionMode::Replace);
+        assert_eq!(selected(&mut app), vec![own]);
+    }
+
+    #[test]
+    fn it_adds_to_selection() {
+        let mut app = setup();
+        let a = app.world.spawn(Selected).id();
+        let b = app.world.spawn_empty().id();
+        select(&mut app, vec![b], SelectionMode::Add);
+        assert_eq!(selected(&mut app), vec![a, b]);
+    }
+
+    #[test]
+    fn it_toggles_selection() {
+        let mut app = setup();
+        let a = app.world.spawn(Selected).id();
+        let b = app.world.spawn_empty().id();
+        select(&mut app, vec![a, b], SelectionMode::Toggle);
+        assert_eq!(selected(&mut app), vec![b]);
+    }
+
+    #[test]
+    fn it_clears_selection_when_replacing_with_nothing() {
+        let mut app = setup();
+        app.world.spawn(Selected);
+        select(&mut app, vec![], SelectionMode::Replace);
+        assert!(selected(&mut app).is_empty());
+    }
+
+    #[test]
+    fn it_sends_selection_changed_event() {
+        let mut app = setup();
+        let a = app.world.spawn_empty().id();
+        select(&mut app, vec![a], SelectionMode::Replace);
+        let events = app.world.resource::<Events<SelectionChanged>>();
+        let changed: Vec<&SelectionChanged> = events.get_reader().iter(events).collect();
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].selected, vec![a]);
+    }
+
+    #[test]
+    fn it_doesnt_send_selection_changed_when_nothing_changed() {
+        let mut app = setup();
+        let a = app.world.spawn(Selected).id();
+        select(&mut app, vec![a], SelectionMode::Add);
+        let events = app.world.resource::<Events<SelectionChanged>>();
+        assert!(events.is_empty());
+    }
+}
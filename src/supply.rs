@@ -1,6 +1,30 @@
+use bevy::prelude::*;
+use crate::game_state::match_running;
+use crate::player::{Owner, Player};
+use crate::production::ProductionQueue;
+
+pub struct SupplyPlugin;
+
+impl Plugin for SupplyPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .register_type::<SupplyProvider>()
+            .register_type::<SupplyCost>()
+            .add_systems(Update, update_supply.run_if(match_running));
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub struct NotEnoughSupplyError;
 
+/// Supply capacity added by a building, such as a depot or housing.
+#[derive(Component, Reflect)]
+pub struct SupplyProvider(pub u32);
+
+/// Supply used by a unit for as long as it's alive.
+#[derive(Component, Reflect)]
+pub struct SupplyCost(pub u32);
+
 impl Supply {
     pub fn amount(&self) -> u32 {
         self.0
@@ -10,14 +34,24 @@ impl Supply {
         self.1
     }
 
-    pub fn add_amount(&mut self, x: u32) -> Result<(), NotEnoughSupplyError> {
+    pub fn can_add_amount(&self, x: u32) -> Result<(), NotEnoughSupplyError> {
         if self.0 + x > self.1 {
             return Err(NotEnoughSupplyError);
         }
+        Ok(())
+    }
+
+    pub fn add_amount(&mut self, x: u32) -> Result<(), NotEnoughSupplyError> {
+        self.can_add_amount(x)?;
         self.0 += x;
         Ok(())
     }
 
+    /// Sets the amount used even above capacity, e.g. after losing a supply building.
+    pub fn set_amount(&mut self, x: u32) {
+        self.0 = x;
+    }
+
     pub fn remove_amount(&mut self, x: u32) {
         if x > self.0 {
             self.0 = 0;
@@ -39,9 +73,39 @@ impl Supply {
     }
 }
 
+// Recount each player's supply from their living units, units in training and buildings,
+// only touching `Supply` when it changed.
+fn update_supply(
+    mut supply_q: Query<(&Player, &mut Supply)>,
+    providers_q: Query<(&SupplyProvider, &Owner)>,
+    costs_q: Query<(&SupplyCost, &Owner)>,
+    queues_q: Query<(&ProductionQueue, &Owner)>,
+) {
+    for (player, mut supply) in supply_q.iter_mut() {
+        let owned = |owner: &Owner| owner.0 == player.id;
+        let capacity: u32 = providers_q.iter()
+            .filter(|(_, owner)| owned(owner))
+            .map(|(provider, _)| provider.0)
+            .sum();
+        let amount: u32 = costs_q.iter().filter(|(_, owner)| owned(owner)).map(|(cost, _)| cost.0).sum::<u32>()
+            + queues_q.iter().filter(|(_, owner)| owned(owner)).map(|(queue, _)| queue.reserved_supply()).sum::<u32>();
+        if supply.capacity() != capacity {
+            let current = supply.capacity();
+            supply.remove_capacity(current);
+            supply.add_capacity(capacity);
+        }
+        if supply.amount() != amount {
+            supply.set_amount(amount);
+        }
+    }
+}
+
 #[cfg(test)]
 mod supply_tests {
-    use crate::supply::Supply;
+    use bevy::prelude::*;
+    use crate::game_state::{AppState, MatchState};
+    use crate::player::{Owner, Player, PlayerId, Team};
+    use crate::supply::{Supply, SupplyCost, SupplyPlugin, SupplyProvider};
     use crate::supply::NotEnoughSupplyError;
 
     #[test]
@@ -109,7 +173,45 @@ mod supply_tests {
         supply.remove_amount(1);
         assert_eq!(supply.amount(), 0);
     }
+
+    #[test]
+    fn it_can_check_amount_without_adding_it() {
+        let mut supply = Supply::default();
+        supply.add_capacity(1);
+        assert_eq!(supply.can_add_amount(2).unwrap_err(), NotEnoughSupplyError);
+        assert!(supply.can_add_amount(1).is_ok());
+        assert_eq!(supply.amount(), 0);
+    }
+
+    #[test]
+    fn it_can_set_amount_above_capacity() {
+        let mut supply = Supply::default();
+        supply.set_amount(3);
+        assert_eq!(supply.amount(), 3);
+    }
+
+    #[test]
+    fn it_counts_supply_from_units_and_buildings() {
+        let mut app = App::new();
+        app
+            .add_state::<AppState>()
+            .add_state::<MatchState>()
+            .insert_resource(NextState(Some(AppState::InGame)))
+            .add_plugins((MinimalPlugins, SupplyPlugin));
+        let owner = Owner(PlayerId(0));
+        let player = app.world.spawn((Player { id: PlayerId(0), team: Team(0) }, Supply::default())).id();
+        app.world.spawn((SupplyProvider(10), owner));
+        app.world.spawn((SupplyCost(1), owner));
+        app.world.spawn((SupplyCost(2), owner));
+        // Another player's units don't use up this player's supply.
+        app.world.spawn((SupplyCost(4), Owner(PlayerId(1))));
+        app.update();
+
+        let supply = app.world.get::<Supply>(player).unwrap();
+        assert_eq!(supply.amount(), 3);
+        assert_eq!(supply.capacity(), 10);
+    }
 }
 
-#[derive(Default)]
+#[derive(Default, Component, Debug)]
 pub struct Supply(u32, u32);
@@ -0,0 +1,217 @@
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use crate::game_state::{despawn_with, AppState, GameOutcome, MatchState};
+
+const BUTTON_COLOR: Color = Color::rgb(0.15, 0.15, 0.15);
+const BUTTON_HOVERED_COLOR: Color = Color::rgb(0.3, 0.3, 0.3);
+
+/// Full-screen menus for the states around a match: main menu, loading, pause and game over.
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_systems(OnEnter(AppState::MainMenu), setup_main_menu)
+            .add_systems(OnEnter(AppState::Loading), setup_loading_screen)
+            .add_systems(OnEnter(MatchState::Paused), setup_pause_menu)
+            .add_systems(OnEnter(AppState::GameOver), setup_game_over_screen)
+            .add_systems(OnExit(AppState::MainMenu), despawn_with::<MenuScreen>)
+            .add_systems(OnExit(AppState::Loading), despawn_with::<MenuScreen>)
+            .add_systems(OnExit(MatchState::Paused), despawn_with::<PauseMenu>)
+            .add_systems(OnExit(AppState::GameOver), despawn_with::<MenuScreen>)
+            .add_systems(Update, menu_button_system);
+    }
+}
+
+/// Root of the menu shown for the current state.
+#[derive(Component)]
+struct MenuScreen;
+
+/// Pause menu, closed on its own: surrendering opens the game over screen before the match leaves the pause.
+#[derive(Component)]
+struct PauseMenu;
+
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MenuButton {
+    Play,
+    Resume,
+    /// Gives up the match, ending it in defeat.
+    Surrender,
+    MainMenu,
+    Quit,
+}
+
+impl MenuButton {
+    fn label(&self) -> &'static str {
+        match self {
+            MenuButton::Play => "Play",
+            MenuButton::Resume => "Resume",
+            MenuButton::Surrender => "Surrender",
+            MenuButton::MainMenu => "Main menu",
+            MenuButton::Quit => "Quit",
+        }
+    }
+}
+
+fn spawn_menu(commands: &mut Commands, title: &str, buttons: &[MenuButton]) -> Entity {
+    commands.spawn((
+        MenuScreen,
+        NodeBundle {
+            style: Style {
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                row_gap: Val::Px(12.),
+                ..default()
+            },
+            background_color: Color::rgba(0., 0., 0., 0.6).into(),
+            ..default()
+        },
+    )).with_children(|parent| {
+        parent.spawn(TextBundle::from_section(
+            title.to_string(),
+            TextStyle {
+                color: Color::ALICE_BLUE,
+                font_size: 40.,
+                ..default()
+            },
+        ));
+        for button in buttons {
+            parent.spawn((
+                *button,
+                ButtonBundle {
+                    style: Style {
+                        width: Val::Px(160.),
+                        padding: UiRect::all(Val::Px(8.)),
+                        justify_content: JustifyContent::Center,
+                        ..default()
+                    },
+                    background_color: BUTTON_COLOR.into(),
+                    ..default()
+                },
+            )).with_children(|parent| {
+                parent.spawn(TextBundle::from_section(
+                    button.label(),
+                    TextStyle {
+                        color: Color::ALICE_BLUE,
+                        font_size: 18.,
+                        ..default()
+                    },
+                ));
+            });
+        }
+    }).id()
+}
+
+fn setup_main_menu(mut commands: Commands) {
+    spawn_menu(&mut commands, "Top-Down Pathfinding", &[MenuButton::Play, MenuButton::Quit]);
+}
+
+fn setup_loading_screen(mut commands: Commands) {
+    spawn_menu(&mut commands, "Loading...", &[]);
+}
+
+fn setup_pause_menu(mut commands: Commands) {
+    let menu = spawn_menu(&mut commands, "Paused", &[MenuButton::Resume, MenuButton::Surrender]);
+    commands.entity(menu).insert(PauseMenu);
+}
+
+fn setup_game_over_screen(mut commands: Commands, outcome: Option<Res<GameOutcome>>) {
+    let title = match outcome.as_deref() {
+        Some(GameOutcome::Victory) => "Victory",
+        _ => "Defeat",
+    };
+    spawn_menu(&mut commands, title, &[MenuButton::Play, MenuButton::MainMenu]);
+}
+
+fn menu_button_system(
+    mut commands: Commands,
+    mut buttons_q: Query<(&Interaction, &MenuButton, &mut BackgroundColor), Changed<Interaction>>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut next_match_state: ResMut<NextState<MatchState>>,
+    mut app_exit: EventWriter<AppExit>,
+) {
+    for (interaction, button, mut background) in buttons_q.iter_mut() {
+        *background = match interaction {
+            Interaction::Hovered => BUTTON_HOVERED_COLOR.into(),
+            _ => BUTTON_COLOR.into(),
+        };
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match button {
+            MenuButton::Play => next_state.set(AppState::Loading),
+            MenuButton::Resume => next_match_state.set(MatchState::Running),
+            MenuButton::Surrender => {
+                commands.insert_resource(GameOutcome::Defeat);
+                next_state.set(AppState::GameOver);
+            }
+            MenuButton::MainMenu => next_state.set(AppState::MainMenu),
+            MenuButton::Quit => app_exit.send(AppExit),
+        }
+    }
+}
+
+#[cfg(test)]
+mod menu_test {
+    use bevy::core_pipeline::core_2d::Core2dPlugin;
+    use bevy::prelude::*;
+    use crate::game_state::{AppState, GameStatePlugin, MatchState};
+    use crate::input::ActionState;
+    use crate::ui::menu::{MenuButton, MenuPlugin};
+
+    fn setup() -> App {
+        let mut app = App::new();
+        app.add_plugins((Core2dPlugin, MenuPlugin));
+        app.add_state::<AppState>();
+        app.add_state::<MatchState>();
+        app.update();
+        app
+    }
+
+    fn press(app: &mut App, button: MenuButton) {
+        let entity = app.world.query::<(Entity, &MenuButton)>()
+            .iter(&app.world)
+            .find(|(_, menu_button)| **menu_button == button)
+            .map(|(entity, _)| entity)
+            .unwrap();
+        *app.world.get_mut::<Interaction>(entity).unwrap() = Interaction::Pressed;
+        app.update();
+        app.update();
+    }
+
+    #[test]
+    fn it_starts_loading_from_the_main_menu() {
+        let mut app = setup();
+        press(&mut app, MenuButton::Play);
+        assert_eq!(*app.world.resource::<State<AppState>>().get(), AppState::Loading);
+    }
+
+    #[test]
+    fn it_replaces_the_menu_when_the_state_changes() {
+        let mut app = setup();
+        press(&mut app, MenuButton::Play);
+        assert!(app.world.query::<&MenuButton>().iter(&app.world).all(|button| *button != MenuButton::Play));
+    }
+
+    #[test]
+    fn it_shows_the_game_over_screen_when_surrendering_while_paused() {
+        let mut app = setup();
+        app.add_plugins(GameStatePlugin).init_resource::<ActionState>();
+        app.world.resource_mut::<NextState<AppState>>().set(AppState::InGame);
+        app.update();
+        app.world.resource_mut::<NextState<MatchState>>().set(MatchState::Paused);
+        app.update();
+        press(&mut app, MenuButton::Surrender);
+        app.update();
+
+        assert_eq!(*app.world.resource::<State<AppState>>().get(), AppState::GameOver);
+        assert_eq!(*app.world.resource::<State<MatchState>>().get(), MatchState::Running);
+        let buttons: Vec<MenuButton> = app.world.query::<&MenuButton>().iter(&app.world).copied().collect();
+        assert!(buttons.contains(&MenuButton::MainMenu));
+        assert!(!buttons.contains(&MenuButton::Resume));
+    }
+}
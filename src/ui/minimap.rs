@@ -0,0 +1,432 @@
+use bevy::prelude::*;
+use bevy::ui::RelativeCursorPosition;
+use bevy::utils::HashMap;
+use crate::building::{Building, Footprint};
+use crate::camera::{CameraCommand, MainCamera};
+use crate::game_state::{match_running, AppState, MatchEntity};
+use crate::harvest::GoldMine;
+use crate::input::{ActionState, InputAction};
+use crate::map::MapSize;
+use crate::orders::MoveEvent;
+use crate::player::{LocalPlayer, Owner, Player, Relation};
+use crate::unit::Unit;
+
+/// Width and height of the minimap in logical pixels.
+const MINIMAP_SIZE: f32 = 180.;
+const UNIT_DOT_SIZE: f32 = 4.;
+/// Fog cells along each side of the map.
+pub const FOG_CELLS: usize = 20;
+/// Distance around the local player's units and buildings that gets explored.
+const VISION_RADIUS: f32 = 12.;
+
+const PLAYER_COLOR: Color = Color::rgb(0.2, 0.9, 0.3);
+const BUILDING_COLOR: Color = Color::rgb(0.1, 0.6, 0.2);
+const ALLY_COLOR: Color = Color::rgb(0.2, 0.5, 0.9);
+const ALLY_BUILDING_COLOR: Color = Color::rgb(0.1, 0.3, 0.6);
+const ENEMY_COLOR: Color = Color::rgb(0.9, 0.2, 0.2);
+const ENEMY_BUILDING_COLOR: Color = Color::rgb(0.6, 0.1, 0.1);
+const GOLD_MINE_COLOR: Color = Color::rgb(0.9, 0.75, 0.1);
+const OBSTACLE_COLOR: Color = Color::rgb(0.45, 0.45, 0.45);
+const FOG_COLOR: Color = Color::rgb(0.05, 0.05, 0.05);
+
+/// Overview of the whole map: terrain, units and resources under a fog of unexplored areas,
+/// with the camera's view outlined. Left-click pans the camera, right-click moves the selection.
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<MinimapFog>()
+            .add_systems(OnEnter(AppState::InGame), setup_minimap)
+            .add_systems(OnExit(AppState::GameOver), reset_fog)
+            .add_systems(Update, (reveal_fog_system, minimap_click_system).run_if(match_running))
+            .add_systems(Update, (update_minimap_icons, update_minimap_fog, update_minimap_camera_frame));
+    }
+}
+
+#[derive(Component)]
+pub struct Minimap;
+
+/// Layer holding one icon per unit, building, resource node and obstacle, by the entity it shows.
+#[derive(Component, Default)]
+struct MinimapIcons(HashMap<Entity, Entity>);
+
+#[derive(Component)]
+struct MinimapFogCell(usize);
+
+#[derive(Component)]
+struct MinimapCameraFrame;
+
+/// Which parts of the map have been seen, as a grid of `FOG_CELLS` by `FOG_CELLS`.
+#[derive(Resource)]
+pub struct MinimapFog {
+    explored: Vec<bool>,
+}
+
+impl Default for MinimapFog {
+    fn default() -> Self {
+        Self { explored: vec![false; FOG_CELLS * FOG_CELLS] }
+    }
+}
+
+impl MinimapFog {
+    /// Explores every cell whose center is within `radius` of `position`, returning whether any was new.
+    pub fn reveal(&mut self, position: Vec3, radius: f32, map_size: f32) -> bool {
+        let cell_size = map_size / FOG_CELLS as f32;
+        let mut revealed = false;
+        for (index, explored) in self.explored.iter_mut().enumerate().filter(|(_, explored)| !**explored) {
+            let center = Vec2::new(
+                ((index % FOG_CELLS) as f32 + 0.5) * cell_size - map_size / 2.,
+                ((index / FOG_CELLS) as f32 + 0.5) * cell_size - map_size / 2.);
+            if center.distance(Vec2::new(position.x, position.z)) <= radius {
+                *explored = true;
+                revealed = true;
+            }
+        }
+        revealed
+    }
+
+    pub fn is_explored(&self, position: Vec3, map_size: f32) -> bool {
+        let cell = |coordinate: f32| ((coordinate / map_size + 0.5) * FOG_CELLS as f32).floor() as isize;
+        let (x, z) = (cell(position.x), cell(position.z));
+        let range = 0..FOG_CELLS as isize;
+        range.contains(&x) && range.contains(&z) && self.explored[z as usize * FOG_CELLS + x as usize]
+    }
+}
+
+/// Position on the minimap, from (0, 0) at its top left to (1, 1) at its bottom right.
+/// The minimap is oriented like the unrotated camera: +Z is up and -X is right.
+pub fn world_to_minimap(position: Vec3, map_size: f32) -> Vec2 {
+    Vec2::new(0.5 - position.x / map_size, 0.5 - position.z / map_size)
+}
+
+/// Ground position under a point on the minimap.
+pub fn minimap_to_world(normalized: Vec2, map_size: f32) -> Vec3 {
+    Vec3::new((0.5 - normalized.x) * map_size, 0., (0.5 - normalized.y) * map_size)
+}
+
+fn absolute_fill() -> Style {
+    Style {
+        position_type: PositionType::Absolute,
+        width: Val::Percent(100.),
+        height: Val::Percent(100.),
+        ..default()
+    }
+}
+
+fn setup_minimap(mut commands: Commands) {
+    commands.spawn((
+        Minimap,
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(8.),
+                right: Val::Px(8.),
+                width: Val::Px(MINIMAP_SIZE),
+                height: Val::Px(MINIMAP_SIZE),
+                border: UiRect::all(Val::Px(1.)),
+                ..default()
+            },
+            background_color: Color::rgb(0.3, 0.5, 0.3).into(),
+            border_color: Color::ALICE_BLUE.into(),
+            ..default()
+        },
+        Interaction::default(),
+        RelativeCursorPosition::default(),
+        MatchEntity,
+    )).with_children(|parent| {
+        parent.spawn((MinimapIcons::default(), NodeBundle { style: absolute_fill(), ..default() }));
+        parent.spawn(NodeBundle {
+            style: Style {
+                display: Display::Grid,
+                grid_template_columns: RepeatedGridTrack::flex(FOG_CELLS as u16, 1.),
+                grid_template_rows: RepeatedGridTrack::flex(FOG_CELLS as u16, 1.),
+                ..absolute_fill()
+            },
+            ..default()
+        }).with_children(|parent| {
+            for index in 0..FOG_CELLS * FOG_CELLS {
+                parent.spawn((
+                    MinimapFogCell(index),
+                    NodeBundle { background_color: FOG_COLOR.into(), ..default() },
+                ));
+            }
+        });
+        parent.spawn((
+            MinimapCameraFrame,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    border: UiRect::all(Val::Px(1.)),
+                    ..default()
+                },
+                border_color: Color::WHITE.into(),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+        ));
+    });
+}
+
+fn reset_fog(mut fog: ResMut<MinimapFog>) {
+    *fog = MinimapFog::default();
+}
+
+fn reveal_fog_system(
+    map_size: Option<Res<MapSize>>,
+    local: Res<LocalPlayer>,
+    mut fog: ResMut<MinimapFog>,
+    viewers_q: Query<(&Transform, &Owner), Or<(With<Unit>, With<Building>)>>,
+) {
+    let Some(map_size) = map_size else {
+        return;
+    };
+    // Only flag the fog as changed when a cell was explored, so the minimap isn't redrawn every frame.
+    let mut revealed = false;
+    for (transform, _) in viewers_q.iter().filter(|(_, owner)| local.owns(owner)) {
+        revealed |= fog.bypass_change_detection().reveal(transform.translation, VISION_RADIUS, map_size.0);
+    }
+    if revealed {
+        fog.set_changed();
+    }
+}
+
+// Keeps one icon per tracked entity, placed where the entity is on the map and colored by its owner's team.
+// Enemies are only shown where the map has been explored.
+fn update_minimap_icons(
+    mut commands: Commands,
+    map_size: Option<Res<MapSize>>,
+    local: Res<LocalPlayer>,
+    fog: Res<MinimapFog>,
+    players_q: Query<&Player>,
+    mut layer_q: Query<(Entity, &mut MinimapIcons)>,
+    tracked_q: Query<(Entity, &Transform, Option<&Footprint>, Option<&Unit>, Option<&Building>, Option<&GoldMine>, Option<&Owner>), Or<(With<Unit>, With<Footprint>)>>,
+    mut icons_q: Query<(&mut Style, &mut Visibility)>,
+) {
+    let (Some(map_size), Ok((layer, mut icons))) = (map_size, layer_q.get_single_mut()) else {
+        return;
+    };
+    icons.0.retain(|tracked, icon| {
+        let alive = tracked_q.contains(*tracked);
+        if !alive {
+            commands.entity(*icon).despawn_recursive();
+        }
+        alive
+    });
+    for (entity, transform, footprint, unit, building, gold_mine, owner) in tracked_q.iter() {
+        let half_size = match (footprint, unit) {
+            (_, Some(_)) => Vec2::splat(UNIT_DOT_SIZE / 2. / MINIMAP_SIZE),
+            (Some(footprint), None) => footprint.half_extents / map_size.0,
+            (None, None) => continue,
+        };
+        let center = world_to_minimap(transform.translation, map_size.0);
+        let style = Style {
+            position_type: PositionType::Absolute,
+            left: Val::Percent((center.x - half_size.x) * 100.),
+            top: Val::Percent((center.y - half_size.y) * 100.),
+            width: Val::Percent(half_size.x * 200.),
+            height: Val::Percent(half_size.y * 200.),
+            ..default()
+        };
+        let relation = local.relation(owner, players_q.iter());
+        let visibility = if relation == Relation::Enemy && !fog.is_explored(transform.translation, map_size.0) {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        };
+        match icons.0.get(&entity).and_then(|icon| icons_q.get_mut(*icon).ok()) {
+            Some((mut icon_style, mut icon_visibility)) => {
+                if *icon_style != style {
+                    *icon_style = style;
+                }
+                if *icon_visibility != visibility {
+                    *icon_visibility = visibility;
+                }
+            }
+            None => {
+                let color = match (unit, building, gold_mine, relation) {
+                    (Some(_), _, _, Relation::Own) => PLAYER_COLOR,
+                    (Some(_), _, _, Relation::Ally) => ALLY_COLOR,
+                    (Some(_), _, _, Relation::Enemy) => ENEMY_COLOR,
+                    (_, Some(_), _, Relation::Own) => BUILDING_COLOR,
+                    (_, Some(_), _, Relation::Ally) => ALLY_BUILDING_COLOR,
+                    (_, Some(_), _, Relation::Enemy) => ENEMY_BUILDING_COLOR,
+                    (_, _, Some(_), _) => GOLD_MINE_COLOR,
+                    _ => OBSTACLE_COLOR,
+                };
+                let icon = commands.spawn(NodeBundle { style, background_color: color.into(), visibility, ..default() }).id();
+                commands.entity(layer).add_child(icon);
+                icons.0.insert(entity, icon);
+            }
+        }
+    }
+}
+
+fn update_minimap_fog(fog: Res<MinimapFog>, mut cells_q: Query<(&MinimapFogCell, &mut Visibility)>) {
+    if !fog.is_changed() {
+        return;
+    }
+    for (cell, mut visibility) in cells_q.iter_mut() {
+        // The grid's first row is the top of the minimap, which is the far (+Z) edge of the map.
+        let (column, row) = (cell.0 % FOG_CELLS, cell.0 / FOG_CELLS);
+        let index = (FOG_CELLS - 1 - row) * FOG_CELLS + (FOG_CELLS - 1 - column);
+        let hidden = if fog.explored[index] { Visibility::Hidden } else { Visibility::Inherited };
+        if *visibility != hidden {
+            *visibility = hidden;
+        }
+    }
+}
+
+// Outlines the ground area the camera sees, bounded to the minimap.
+fn update_minimap_camera_frame(
+    map_size: Option<Res<MapSize>>,
+    camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut frame_q: Query<(&mut Style, &mut Visibility), With<MinimapCameraFrame>>,
+) {
+    let (Some(map_size), Ok((camera, camera_transform)), Ok((mut style, mut visibility))) =
+        (map_size, camera_q.get_single(), frame_q.get_single_mut()) else {
+        return;
+    };
+    let Some(viewport) = camera.logical_viewport_size() else {
+        return;
+    };
+    let corners: Vec<Vec2> = [Vec2::ZERO, Vec2::new(viewport.x, 0.), Vec2::new(0., viewport.y), viewport]
+        .into_iter()
+        .filter_map(|corner| camera.viewport_to_world(camera_transform, corner))
+        .filter_map(|ray| ray.intersect_plane(Vec3::ZERO, Vec3::Y).map(|distance| ray.get_point(distance)))
+        .map(|ground| world_to_minimap(ground, map_size.0).clamp(Vec2::ZERO, Vec2::ONE))
+        .collect();
+    if corners.len() < 4 {
+        *visibility = Visibility::Hidden;
+        return;
+    }
+    let min = corners.iter().copied().reduce(Vec2::min).unwrap();
+    let max = corners.iter().copied().reduce(Vec2::max).unwrap();
+    style.left = Val::Percent(min.x * 100.);
+    style.top = Val::Percent(min.y * 100.);
+    style.width = Val::Percent((max.x - min.x) * 100.);
+    style.height = Val::Percent((max.y - min.y) * 100.);
+    *visibility = Visibility::Inherited;
+}
+
+// Select on the minimap pans there, and keeps panning while dragged; move sends the selection there.
+fn minimap_click_system(
+    actions: Res<ActionState>,
+    map_size: Option<Res<MapSize>>,
+    minimap_q: Query<&RelativeCursorPosition, With<Minimap>>,
+    mut camera_commands: EventWriter<CameraCommand>,
+    mut move_events: EventWriter<MoveEvent>,
+) {
+    let (Some(map_size), Ok(cursor)) = (map_size, minimap_q.get_single()) else {
+        return;
+    };
+    let Some(normalized) = cursor.normalized.filter(|_| cursor.mouse_over()) else {
+        return;
+    };
+    let target = minimap_to_world(normalized, map_size.0);
+    if actions.pressed(InputAction::Select) {
+        camera_commands.send(CameraCommand::CenterOn(target));
+    }
+    if actions.just_pressed(InputAction::Move) {
+        move_events.send(MoveEvent {
+            target,
+            queue: actions.pressed(InputAction::Queue),
+            entity: None,
+        });
+    }
+}
+
+#[cfg(test)]
+mod minimap_test {
+    use bevy::core_pipeline::core_2d::Core2dPlugin;
+    use bevy::prelude::*;
+    use crate::camera::CameraCommand;
+    use crate::game_state::{AppState, MatchState};
+    use crate::input::ActionState;
+    use crate::map::MapSize;
+    use crate::orders::MoveEvent;
+    use crate::player::{LocalPlayer, Owner, Player, PlayerId, Team};
+    use crate::ui::minimap::{minimap_to_world, world_to_minimap, MinimapFog, MinimapIcons, MinimapPlugin, ALLY_COLOR, ENEMY_COLOR, PLAYER_COLOR};
+    use crate::unit::Unit;
+
+    fn setup() -> App {
+        let mut app = App::new();
+        app
+            .add_plugins((Core2dPlugin, MinimapPlugin))
+            .add_state::<AppState>()
+            .add_state::<MatchState>()
+            .insert_resource(NextState(Some(AppState::InGame)))
+            .insert_resource(MapSize(80.))
+            .init_resource::<ActionState>()
+            .init_resource::<LocalPlayer>()
+            .add_event::<CameraCommand>()
+            .add_event::<MoveEvent>();
+        app.update();
+        app
+    }
+
+    fn icon_color(app: &mut App, entity: Entity) -> Color {
+        let icon = *app.world.query::<&MinimapIcons>().single(&app.world).0.get(&entity).unwrap();
+        app.world.get::<BackgroundColor>(icon).unwrap().0
+    }
+
+    fn icon_visibility(app: &mut App, entity: Entity) -> Visibility {
+        let icon = *app.world.query::<&MinimapIcons>().single(&app.world).0.get(&entity).unwrap();
+        *app.world.get::<Visibility>(icon).unwrap()
+    }
+
+    #[test]
+    fn it_maps_world_positions_to_the_minimap_and_back() {
+        assert_eq!(world_to_minimap(Vec3::ZERO, 80.), Vec2::splat(0.5));
+        // The far left corner, as seen from the default camera, is the minimap's top left.
+        assert_eq!(world_to_minimap(Vec3::new(40., 0., 40.), 80.), Vec2::ZERO);
+        let position = Vec3::new(-10., 0., 30.);
+        assert_eq!(minimap_to_world(world_to_minimap(position, 80.), 80.), position);
+    }
+
+    #[test]
+    fn it_explores_fog_around_a_position() {
+        let mut fog = MinimapFog::default();
+        assert!(!fog.is_explored(Vec3::ZERO, 80.));
+        assert!(fog.reveal(Vec3::new(1., 0., 1.), 8., 80.));
+        assert!(!fog.reveal(Vec3::new(1., 0., 1.), 8., 80.));
+        assert!(fog.is_explored(Vec3::ZERO, 80.));
+        assert!(fog.is_explored(Vec3::new(6., 0., 0.), 80.));
+        assert!(!fog.is_explored(Vec3::new(30., 0., 30.), 80.));
+        assert!(!fog.is_explored(Vec3::new(100., 0., 0.), 80.));
+    }
+
+    #[test]
+    fn it_colors_units_by_their_owners_team() {
+        let mut app = setup();
+        app.world.spawn(Player { id: PlayerId(0), team: Team(0) });
+        app.world.spawn(Player { id: PlayerId(1), team: Team(0) });
+        app.world.spawn(Player { id: PlayerId(2), team: Team(1) });
+        let unit = |owner: u8| (Unit("Worker".to_string()), Transform::default(), Owner(PlayerId(owner)));
+        let own = app.world.spawn(unit(0)).id();
+        let ally = app.world.spawn(unit(1)).id();
+        let enemy = app.world.spawn(unit(2)).id();
+        app.update();
+        app.update();
+
+        assert_eq!(icon_color(&mut app, own), PLAYER_COLOR);
+        assert_eq!(icon_color(&mut app, ally), ALLY_COLOR);
+        assert_eq!(icon_color(&mut app, enemy), ENEMY_COLOR);
+    }
+
+    #[test]
+    fn it_hides_enemies_in_unexplored_areas() {
+        let mut app = setup();
+        app.world.spawn(Player { id: PlayerId(0), team: Team(0) });
+        app.world.spawn(Player { id: PlayerId(1), team: Team(1) });
+        let position = Vec3::new(30., 0., 30.);
+        let enemy = app.world.spawn((Unit("Worker".to_string()), Transform::from_translation(position), Owner(PlayerId(1)))).id();
+        app.update();
+        app.update();
+        assert_eq!(icon_visibility(&mut app, enemy), Visibility::Hidden);
+
+        app.world.resource_mut::<MinimapFog>().reveal(position, 8., 80.);
+        app.update();
+        assert_eq!(icon_visibility(&mut app, enemy), Visibility::Inherited);
+    }
+}
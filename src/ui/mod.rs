@@ -1,21 +1,30 @@
+pub mod menu;
+pub mod minimap;
+pub mod selection_panel;
+
 use bevy::prelude::*;
 use bevy_mod_picking::prelude::Pickable;
-use crate::game_state::AppState;
-use crate::gold_resource::GoldResource;
+use crate::game_speed::GameSpeed;
+use crate::game_state::{match_running, AppState, MatchEntity};
+use crate::player::{LocalPlayer, Player};
+use crate::resources::{ResourceKind, Wallet};
+use crate::supply::Supply;
+use crate::ui::selection_panel::spawn_selection_panel;
 
 pub struct UIPlugin;
 
 impl Plugin for UIPlugin {
     fn build(&self, app: &mut App) {
         app
-            .add_systems(OnEnter(AppState::InGame), setup_gold_resource_ui)
+            .add_systems(OnEnter(AppState::InGame), setup_resources_ui)
             .add_systems(
                 Update,
-                update_gold_resource_label.run_if(in_state(AppState::InGame)));
+                (update_resource_labels, update_supply_label).run_if(match_running))
+            .add_systems(Update, update_game_speed_label);
     }
 }
 
-pub fn setup_gold_resource_ui(mut commands: Commands) {
+pub fn setup_resources_ui(mut commands: Commands) {
     commands.spawn((
         NodeBundle {
             style: Style {
@@ -32,20 +41,48 @@ pub fn setup_gold_resource_ui(mut commands: Commands) {
             },
             ..default()
         },
-        Pickable::IGNORE
+        Pickable::IGNORE,
+        MatchEntity,
     )).with_children(|parent| {
         parent.spawn(NodeBundle {
             style: Style {
                 display: Display::Grid,
                 grid_column: GridPlacement::span(1),
+                grid_template_columns: RepeatedGridTrack::auto(ResourceKind::ALL.len() as u16 + 2),
+                column_gap: Val::Px(12.),
+                justify_content: JustifyContent::Start,
                 ..default()
             },
             ..default()
         }).with_children(|parent| {
+            for kind in ResourceKind::ALL {
+                parent.spawn((
+                    ResourceLabel(kind),
+                    TextBundle::from_section(
+                        resource_label(kind, 0, 0.),
+                        TextStyle {
+                            color: Color::ALICE_BLUE,
+                            font_size: 14.,
+                            ..default()
+                        },
+                    )
+                ));
+            }
+            parent.spawn((
+                SupplyLabel,
+                TextBundle::from_section(
+                    "0 / 0".to_string(),
+                    TextStyle {
+                        color: Color::ALICE_BLUE,
+                        font_size: 14.,
+                        ..default()
+                    },
+                )
+            ));
             parent.spawn((
-                GoldResourceLabel,
+                GameSpeedLabel,
                 TextBundle::from_section(
-                    "0".to_string(),
+                    "1x".to_string(),
                     TextStyle {
                         color: Color::ALICE_BLUE,
                         font_size: 14.,
@@ -54,42 +91,96 @@ pub fn setup_gold_resource_ui(mut commands: Commands) {
                 )
             ));
         });
+        spawn_selection_panel(parent);
     });
 }
 
-pub fn update_gold_resource_label(
-    query: Query<&GoldResource, Changed<GoldResource>>,
-    mut text_query: Query<&mut Text, With<GoldResourceLabel>>,
+/// A resource's balance, followed by its income once it has one.
+pub fn resource_label(kind: ResourceKind, balance: u32, income: f32) -> String {
+    if income > 0. {
+        format!("{} {} (+{:.1}/s)", kind.name(), balance, income)
+    } else {
+        format!("{} {}", kind.name(), balance)
+    }
+}
+
+// Income moves every second, so the labels are compared instead of waiting for the wallet to change.
+pub fn update_resource_labels(
+    local: Res<LocalPlayer>,
+    query: Query<(&Player, &Wallet)>,
+    mut text_query: Query<(&ResourceLabel, &mut Text)>,
+) {
+    let Some((_, wallet)) = query.iter().find(|(player, _)| player.id == local.0) else {
+        return;
+    };
+    for (label, mut text) in text_query.iter_mut() {
+        let value = resource_label(label.0, wallet.balance(label.0), wallet.income(label.0));
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct ResourceLabel(pub ResourceKind);
+
+pub fn update_supply_label(
+    local: Res<LocalPlayer>,
+    query: Query<(&Player, &Supply), Changed<Supply>>,
+    mut text_query: Query<&mut Text, With<SupplyLabel>>,
 ) {
     let mut text = text_query.single_mut();
-    for gold_resource in query.iter() {
-        text.sections[0].value = gold_resource.balance().to_string();
+    for (_, supply) in query.iter().filter(|(player, _)| player.id == local.0) {
+        text.sections[0].value = format!("{} / {}", supply.amount(), supply.capacity());
     }
 }
 
 #[derive(Component)]
-pub struct GoldResourceLabel;
+pub struct SupplyLabel;
+
+// Runs in every state, so speed changes made while paused show right away.
+pub fn update_game_speed_label(
+    speed: Option<Res<GameSpeed>>,
+    mut text_query: Query<&mut Text, With<GameSpeedLabel>>,
+) {
+    let Some(speed) = speed.filter(|speed| speed.is_changed()) else {
+        return;
+    };
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value = format!("{}x", speed.get());
+    }
+}
+
+#[derive(Component)]
+pub struct GameSpeedLabel;
 
 #[cfg(test)]
 mod resources_ui_test {
     use bevy::core_pipeline::core_2d::Core2dPlugin;
     use bevy::prelude::*;
-    use crate::game_state::AppState;
-    use crate::gold_resource::GoldResource;
-    use crate::ui::{GoldResourceLabel, UIPlugin};
+    use crate::game_state::{AppState, MatchState};
+    use crate::player::{LocalPlayer, Player, PlayerId, Team};
+    use crate::resources::{ResourceKind, Wallet};
+    use crate::supply::Supply;
+    use crate::ui::{resource_label, ResourceLabel, SupplyLabel, UIPlugin};
 
     #[test]
-    fn it_shows_gold_resources_label() {
+    fn it_shows_a_label_per_resource_kind() {
         let mut app = setup();
-        assert_eq!(app.world.query::<&GoldResourceLabel>()
-                       .iter(&app.world).len(), 1);
+        assert_eq!(app.world.query::<&ResourceLabel>()
+                       .iter(&app.world).len(), ResourceKind::ALL.len());
     }
 
     #[test]
-    fn it_shows_gold_resources_label_default_0() {
+    fn it_shows_resource_labels_default_0() {
         let mut app = setup();
-        let text = get_resource_label_text_value(&mut app);
-        assert_eq!(*text, "0");
+        let text = get_resource_label_text_value(&mut app, ResourceKind::Fuel);
+        assert_eq!(*text, "Fuel 0");
+    }
+
+    #[test]
+    fn it_shows_income_next_to_the_balance() {
+        assert_eq!(resource_label(ResourceKind::Gold, 120, 2.5), "Gold 120 (+2.5/s)");
     }
 
 
@@ -97,28 +188,70 @@ mod resources_ui_test {
     fn it_updates_label_when_resource_changes() {
         //setup
         let mut app = setup();
-        let entity = app.world.spawn(GoldResource::new(0)).id();
+        let entity = app.world.spawn((Player { id: PlayerId(0), team: Team(0) }, Wallet::default())).id();
 
         // action
         let mut binding = app.world.entity_mut(entity);
-        let mut gold_resource = binding.get_mut::<GoldResource>().unwrap();
-        gold_resource.gain(50);
+        let mut wallet = binding.get_mut::<Wallet>().unwrap();
+        wallet.gain(ResourceKind::Gold, 50);
         app.update();
 
         //result
-        assert_eq!(get_resource_label_text_value(&mut app), "50");
+        assert_eq!(get_resource_label_text_value(&mut app, ResourceKind::Gold), "Gold 50");
+    }
+
+    #[test]
+    fn it_only_shows_the_local_players_wallet() {
+        let mut app = setup();
+        app.world.spawn((Player { id: PlayerId(0), team: Team(0) }, Wallet::default()));
+        let mut other = Wallet::default();
+        other.gain(ResourceKind::Gold, 70);
+        app.world.spawn((Player { id: PlayerId(1), team: Team(1) }, other));
+        app.update();
+
+        assert_eq!(get_resource_label_text_value(&mut app, ResourceKind::Gold), "Gold 0");
     }
 
-    fn get_resource_label_text_value(app: &mut App) -> &String {
-        let text = app.world.query_filtered::<&Text, With<GoldResourceLabel>>()
+    #[test]
+    fn it_shows_supply_label_default_empty() {
+        let mut app = setup();
+        assert_eq!(get_supply_label_text_value(&mut app), "0 / 0");
+    }
+
+    #[test]
+    fn it_updates_supply_label_when_supply_changes() {
+        let mut app = setup();
+        let mut supply = Supply::default();
+        supply.add_capacity(10);
+        supply.add_amount(3).unwrap();
+        app.world.spawn((Player { id: PlayerId(0), team: Team(0) }, supply));
+        app.update();
+
+        assert_eq!(get_supply_label_text_value(&mut app), "3 / 10");
+    }
+
+    fn get_supply_label_text_value(app: &mut App) -> &String {
+        let text = app.world.query_filtered::<&Text, With<SupplyLabel>>()
             .single(&app.world);
         &text.sections[0].value
     }
 
+    fn get_resource_label_text_value(app: &mut App, kind: ResourceKind) -> &String {
+        let text = app.world.query::<(&ResourceLabel, &Text)>()
+            .iter(&app.world)
+            .find(|(label, _)| label.0 == kind)
+            .map(|(_, text)| text)
+            .unwrap();
+        &text.sections[0].value
+    }
+
     fn setup() -> App {
         let mut app = App::new();
         app.add_plugins((Core2dPlugin, UIPlugin));
+        app.init_resource::<LocalPlayer>();
         app.add_state::<AppState>();
+        app.add_state::<MatchState>();
+        app.insert_resource(NextState(Some(AppState::InGame)));
 
         app.update();
         app
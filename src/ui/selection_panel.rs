@@ -0,0 +1,420 @@
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use bevy::ui::UiSystem;
+use crate::building::{Building, BuildingKind};
+use crate::game_state::match_running;
+use crate::harvest::{Worker, WorkerState};
+use crate::input::{update_action_state, ActionState, InputAction};
+use crate::movement::MovementPath;
+use crate::orders::{HoldPosition, OrderQueue, OrderTargeting};
+use crate::production::ProductionQueue;
+use crate::selection::{SelectEvent, Selected, SelectionMode};
+use crate::unit::{Health, Unit};
+
+/// Portraits shown before the rest of the selection is summed up as "+N".
+const MAX_PORTRAITS: usize = 8;
+const PORTRAIT_COLOR: Color = Color::rgb(0.15, 0.15, 0.15);
+const BUTTON_COLOR: Color = Color::rgb(0.2, 0.2, 0.25);
+const HOVERED_COLOR: Color = Color::rgb(0.3, 0.3, 0.35);
+const HEALTH_COLOR: Color = Color::rgb(0.2, 0.8, 0.3);
+
+/// Bottom bar listing the selected units with their health and orders, and a command card with
+/// buttons for what the whole selection can do.
+pub struct SelectionPanelPlugin;
+
+impl Plugin for SelectionPanelPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            // Buttons press their hotkey's action, so they run between reading input and the gameplay systems.
+            .add_systems(
+                PreUpdate,
+                panel_button_system
+                    .after(update_action_state)
+                    .after(UiSystem::Focus)
+                    .after(InputSystem)
+                    .run_if(match_running))
+            .add_systems(
+                Update,
+                (rebuild_selection_panel, update_selection_panel).chain().run_if(match_running));
+    }
+}
+
+#[derive(Component)]
+pub struct SelectionPanel;
+
+#[derive(Component)]
+struct PortraitList;
+
+#[derive(Component)]
+struct CommandCard;
+
+/// Button narrowing the selection down to the entity it shows.
+#[derive(Component)]
+struct Portrait(Entity);
+
+#[derive(Component)]
+struct HealthBar(Entity);
+
+#[derive(Component)]
+struct OrderLabel(Entity);
+
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CommandButton {
+    Move,
+    Stop,
+    Hold,
+    /// Targets a gold mine, which is a move order onto it.
+    Gather,
+    Build(BuildingKind),
+}
+
+impl CommandButton {
+    fn label(&self) -> String {
+        match self {
+            CommandButton::Move => "Move".to_string(),
+            CommandButton::Stop => "Stop".to_string(),
+            CommandButton::Hold => "Hold".to_string(),
+            CommandButton::Gather => "Gather".to_string(),
+            CommandButton::Build(kind) => format!("Build {}", kind.name()),
+        }
+    }
+}
+
+/// Commands an entity offers, in command card order.
+pub fn commands_of(unit: bool, worker: bool) -> Vec<CommandButton> {
+    let mut commands = Vec::new();
+    if unit {
+        commands.extend([CommandButton::Move, CommandButton::Stop, CommandButton::Hold]);
+    }
+    if worker {
+        commands.push(CommandButton::Gather);
+        commands.extend(BuildingKind::ALL.map(CommandButton::Build));
+    }
+    commands
+}
+
+/// Commands every one of the given entities offers.
+pub fn common_commands(mut selection: impl Iterator<Item = Vec<CommandButton>>) -> Vec<CommandButton> {
+    let Some(first) = selection.next() else {
+        return Vec::new();
+    };
+    selection.fold(first, |common, commands| {
+        common.into_iter().filter(|command| commands.contains(command)).collect()
+    })
+}
+
+/// What an entity is doing right now, followed by how many more orders it has queued.
+pub fn order_label(
+    moving: bool,
+    holding: bool,
+    worker_state: Option<&WorkerState>,
+    training: Option<&str>,
+    queued: usize,
+) -> String {
+    let current = match (worker_state, training) {
+        (Some(WorkerState::MovingToMine), _) => "Going to mine".to_string(),
+        (Some(WorkerState::Harvesting(_)), _) => "Harvesting".to_string(),
+        (Some(WorkerState::ReturningToDepot), _) => "Returning gold".to_string(),
+        (_, Some(name)) => format!("Training {name}"),
+        _ if holding => "Holding".to_string(),
+        _ if moving => "Moving".to_string(),
+        _ => "Idle".to_string(),
+    };
+    if queued > 0 {
+        format!("{current} (+{queued})")
+    } else {
+        current
+    }
+}
+
+/// Spawns the empty panel into the HUD's bottom row; its contents follow the selection.
+pub fn spawn_selection_panel(parent: &mut ChildBuilder) {
+    parent.spawn((
+        SelectionPanel,
+        NodeBundle {
+            style: Style {
+                grid_row: GridPlacement::start(3),
+                justify_content: JustifyContent::SpaceBetween,
+                padding: UiRect::all(Val::Px(6.)),
+                ..default()
+            },
+            background_color: Color::rgba(0., 0., 0., 0.7).into(),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        // Keeps clicks on the panel's background from reaching the world.
+        Interaction::default(),
+    )).with_children(|parent| {
+        let row = || Style {
+            flex_wrap: FlexWrap::Wrap,
+            align_content: AlignContent::Start,
+            row_gap: Val::Px(4.),
+            column_gap: Val::Px(4.),
+            ..default()
+        };
+        parent.spawn((PortraitList, NodeBundle { style: row(), ..default() }));
+        parent.spawn((
+            CommandCard,
+            NodeBundle {
+                style: Style {
+                    max_width: Val::Px(300.),
+                    justify_content: JustifyContent::End,
+                    ..row()
+                },
+                ..default()
+            },
+        ));
+    });
+}
+
+fn text(value: impl Into<String>, font_size: f32) -> TextBundle {
+    TextBundle::from_section(
+        value,
+        TextStyle {
+            color: Color::ALICE_BLUE,
+            font_size,
+            ..default()
+        },
+    )
+}
+
+// Respawns the portraits and command buttons whenever something is selected or deselected.
+fn rebuild_selection_panel(
+    mut commands: Commands,
+    added_q: Query<(), Added<Selected>>,
+    mut removed: RemovedComponents<Selected>,
+    selected_q: Query<(Entity, Option<&Unit>, Option<&Building>, Option<&Health>, Option<&MovementPath>, Option<&Worker>), With<Selected>>,
+    mut panel_q: Query<&mut Visibility, With<SelectionPanel>>,
+    portraits_q: Query<Entity, With<PortraitList>>,
+    card_q: Query<Entity, With<CommandCard>>,
+) {
+    let deselected = removed.iter().count() > 0;
+    if added_q.is_empty() && !deselected {
+        return;
+    }
+    let (Ok(mut visibility), Ok(portraits), Ok(card)) = (panel_q.get_single_mut(), portraits_q.get_single(), card_q.get_single()) else {
+        return;
+    };
+    let mut selected: Vec<_> = selected_q.iter().collect();
+    selected.sort_by_key(|(entity, ..)| *entity);
+    *visibility = if selected.is_empty() { Visibility::Hidden } else { Visibility::Inherited };
+
+    commands.entity(portraits).despawn_descendants();
+    commands.entity(portraits).with_children(|parent| {
+        for (entity, unit, building, health, ..) in selected.iter().take(MAX_PORTRAITS) {
+            let name = match (unit, building) {
+                (Some(unit), _) => unit.0.clone(),
+                (None, Some(building)) => building.0.name().to_string(),
+                (None, None) => continue,
+            };
+            parent.spawn((
+                Portrait(*entity),
+                ButtonBundle {
+                    style: Style {
+                        width: Val::Px(110.),
+                        flex_direction: FlexDirection::Column,
+                        padding: UiRect::all(Val::Px(4.)),
+                        row_gap: Val::Px(2.),
+                        ..default()
+                    },
+                    background_color: PORTRAIT_COLOR.into(),
+                    ..default()
+                },
+            )).with_children(|parent| {
+                parent.spawn(text(name, 14.));
+                if health.is_some() {
+                    parent.spawn(NodeBundle {
+                        style: Style {
+                            width: Val::Percent(100.),
+                            height: Val::Px(6.),
+                            ..default()
+                        },
+                        background_color: Color::rgb(0.4, 0.1, 0.1).into(),
+                        ..default()
+                    }).with_children(|parent| {
+                        parent.spawn((
+                            HealthBar(*entity),
+                            NodeBundle {
+                                style: Style { height: Val::Percent(100.), ..default() },
+                                background_color: HEALTH_COLOR.into(),
+                                ..default()
+                            },
+                        ));
+                    });
+                }
+                parent.spawn((OrderLabel(*entity), text("", 12.)));
+            });
+        }
+        if selected.len() > MAX_PORTRAITS {
+            parent.spawn(text(format!("+{}", selected.len() - MAX_PORTRAITS), 14.));
+        }
+    });
+
+    let available = common_commands(selected.iter()
+        .map(|(_, _, _, _, path, worker)| commands_of(path.is_some(), worker.is_some())));
+    commands.entity(card).despawn_descendants();
+    commands.entity(card).with_children(|parent| {
+        for command in available {
+            parent.spawn((
+                command,
+                ButtonBundle {
+                    style: Style {
+                        width: Val::Px(92.),
+                        padding: UiRect::all(Val::Px(4.)),
+                        justify_content: JustifyContent::Center,
+                        ..default()
+                    },
+                    background_color: BUTTON_COLOR.into(),
+                    ..default()
+                },
+            )).with_children(|parent| {
+                parent.spawn(text(command.label(), 12.));
+            });
+        }
+    });
+}
+
+// Keeps health bars and order labels in sync with the units they show.
+fn update_selection_panel(
+    units_q: Query<(Option<&Health>, Option<&MovementPath>, Option<&HoldPosition>, Option<&OrderQueue>, Option<&Worker>, Option<&ProductionQueue>)>,
+    mut bars_q: Query<(&HealthBar, &mut Style)>,
+    mut labels_q: Query<(&OrderLabel, &mut Text)>,
+) {
+    for (bar, mut style) in bars_q.iter_mut() {
+        let Ok((Some(health), ..)) = units_q.get(bar.0) else {
+            continue;
+        };
+        let width = Val::Percent(health.current as f32 / health.max.max(1) as f32 * 100.);
+        if style.width != width {
+            style.width = width;
+        }
+    }
+    for (label, mut text) in labels_q.iter_mut() {
+        let Ok((_, path, hold, order_queue, worker, production)) = units_q.get(label.0) else {
+            continue;
+        };
+        let training = production.and_then(|production| production.jobs().next());
+        let queued = order_queue.map_or(0, |order_queue| order_queue.iter().count())
+            + production.map_or(0, |production| production.len().saturating_sub(1));
+        let value = order_label(
+            path.is_some_and(|path| !path.is_empty()),
+            hold.is_some(),
+            worker.map(Worker::state),
+            training.map(|job| job.name.as_str()),
+            queued,
+        );
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
+
+// Portraits narrow the selection; command buttons press their hotkey's action or wait for a target.
+fn panel_button_system(
+    mut actions: ResMut<ActionState>,
+    mut targeting: ResMut<OrderTargeting>,
+    mut portraits_q: Query<(&Interaction, &Portrait, &mut BackgroundColor), (Changed<Interaction>, Without<CommandButton>)>,
+    mut buttons_q: Query<(&Interaction, &CommandButton, &mut BackgroundColor), Changed<Interaction>>,
+    mut select_events: EventWriter<SelectEvent>,
+) {
+    for (interaction, portrait, mut background) in portraits_q.iter_mut() {
+        *background = match interaction {
+            Interaction::None => PORTRAIT_COLOR.into(),
+            _ => HOVERED_COLOR.into(),
+        };
+        if *interaction == Interaction::Pressed {
+            select_events.send(SelectEvent { entities: vec![portrait.0], mode: SelectionMode::Replace });
+        }
+    }
+    for (interaction, button, mut background) in buttons_q.iter_mut() {
+        *background = match interaction {
+            Interaction::None => BUTTON_COLOR.into(),
+            _ => HOVERED_COLOR.into(),
+        };
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match button {
+            CommandButton::Move | CommandButton::Gather => targeting.0 = true,
+            CommandButton::Stop => actions.trigger(InputAction::Stop),
+            CommandButton::Hold => actions.trigger(InputAction::Hold),
+            CommandButton::Build(kind) => {
+                if let Some(index) = BuildingKind::ALL.iter().position(|other| other == kind) {
+                    actions.trigger(InputAction::PlaceBuilding(index));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod selection_panel_test {
+    use bevy::core_pipeline::core_2d::Core2dPlugin;
+    use bevy::prelude::*;
+    use crate::building::BuildingKind;
+    use crate::game_state::{AppState, MatchState};
+    use crate::harvest::WorkerState;
+    use crate::input::ActionState;
+    use crate::movement::MovementPath;
+    use crate::orders::OrderTargeting;
+    use crate::player::LocalPlayer;
+    use crate::selection::{SelectEvent, Selected, SelectionMode};
+    use crate::ui::selection_panel::{common_commands, commands_of, order_label, CommandButton, Portrait, SelectionPanelPlugin};
+    use crate::ui::UIPlugin;
+    use crate::unit::{Health, Unit};
+
+    fn setup() -> App {
+        let mut app = App::new();
+        app
+            .add_plugins((Core2dPlugin, UIPlugin, SelectionPanelPlugin))
+            .add_state::<AppState>()
+            .add_state::<MatchState>()
+            .insert_resource(NextState(Some(AppState::InGame)))
+            .init_resource::<ActionState>()
+            .init_resource::<OrderTargeting>()
+            .init_resource::<LocalPlayer>()
+            .add_event::<SelectEvent>();
+        app.update();
+        app
+    }
+
+    #[test]
+    fn it_offers_only_the_commands_common_to_the_selection() {
+        let worker = commands_of(true, true);
+        let soldier = commands_of(true, false);
+        assert!(worker.contains(&CommandButton::Build(BuildingKind::Depot)));
+        assert_eq!(common_commands([worker.clone(), soldier].into_iter()),
+                   vec![CommandButton::Move, CommandButton::Stop, CommandButton::Hold]);
+        assert!(common_commands([worker, commands_of(false, false)].into_iter()).is_empty());
+    }
+
+    #[test]
+    fn it_describes_the_current_order_and_queue() {
+        assert_eq!(order_label(false, false, None, None, 0), "Idle");
+        assert_eq!(order_label(true, false, None, None, 2), "Moving (+2)");
+        assert_eq!(order_label(true, false, Some(&WorkerState::ReturningToDepot), None, 0), "Returning gold");
+        assert_eq!(order_label(false, false, None, Some("Soldier"), 1), "Training Soldier (+1)");
+    }
+
+    #[test]
+    fn it_narrows_the_selection_to_a_clicked_portrait() {
+        let mut app = setup();
+        let a = app.world.spawn((Unit("Worker".to_string()), Health::new(10), MovementPath::default(), Selected)).id();
+        app.world.spawn((Unit("Worker".to_string()), Health::new(10), MovementPath::default(), Selected));
+        app.update();
+
+        let portrait = app.world.query::<(Entity, &Portrait)>()
+            .iter(&app.world)
+            .find(|(_, portrait)| portrait.0 == a)
+            .map(|(entity, _)| entity)
+            .unwrap();
+        assert_eq!(app.world.query::<&Portrait>().iter(&app.world).len(), 2);
+        assert_eq!(app.world.query::<&CommandButton>().iter(&app.world).len(), 3);
+
+        *app.world.get_mut::<Interaction>(portrait).unwrap() = Interaction::Pressed;
+        app.update();
+        let events = app.world.resource::<Events<SelectEvent>>();
+        let event = events.get_reader().iter(events).last().unwrap();
+        assert_eq!((event.entities.clone(), event.mode), (vec![a], SelectionMode::Replace));
+    }
+}
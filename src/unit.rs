@@ -0,0 +1,221 @@
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::{TypePath, TypeUuid};
+use bevy::utils::BoxedFuture;
+use bevy_mod_picking::prelude::*;
+use bevy_xpbd_3d::prelude::{CoefficientCombine, Collider, Friction, GravityScale, LockedAxes, Restitution, RigidBody};
+use serde::Deserialize;
+use crate::avoidance::Avoidance;
+use crate::game_state::MatchEntity;
+use crate::harvest::Worker;
+use crate::movement::{MovementPath, MovementStats, StuckTracker};
+use crate::orders::OrderQueue;
+use crate::player::Owner;
+use crate::resources::Cost;
+use crate::selection::Selectable;
+use crate::supply::SupplyCost;
+
+/// Folder under `assets` holding one `.unit.ron` file per unit type.
+pub const UNIT_DEFS_FOLDER: &str = "units";
+
+pub struct UnitPlugin;
+
+impl Plugin for UnitPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_asset::<UnitDef>()
+            .init_asset_loader::<UnitDefLoader>()
+            .register_type::<Unit>()
+            .register_type::<Health>()
+            .add_systems(Startup, load_unit_defs)
+            .add_systems(Update, reload_unit_defs);
+    }
+}
+
+/// A unit type: how it looks, what it costs and how it moves.
+#[derive(Debug, Clone, Deserialize, TypeUuid, TypePath)]
+#[uuid = "6c3b9a52-7f0e-4c1d-9a8e-2f5d4b7e1c30"]
+pub struct UnitDef {
+    /// Unique name buildings and maps refer to the unit type by.
+    pub name: String,
+    pub radius: f32,
+    /// Length of the capsule's cylinder, between the two half spheres.
+    pub height: f32,
+    pub color: Color,
+    pub hp: u32,
+    /// Resources paid when the unit is queued.
+    pub cost: Cost,
+    /// Seconds of training before the unit spawns.
+    pub build_time: f32,
+    pub supply: u32,
+    pub movement: MovementStats,
+    /// Present on unit types that can harvest.
+    #[serde(default)]
+    pub worker: Option<WorkerDef>,
+    #[serde(skip)]
+    pub mesh: Handle<Mesh>,
+    #[serde(skip)]
+    pub material: Handle<StandardMaterial>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct WorkerDef {
+    pub capacity: u32,
+    pub harvest_time: f32,
+}
+
+impl UnitDef {
+    pub fn from_ron(ron: &str) -> Result<Self, ron::error::SpannedError> {
+        ron::from_str(ron)
+    }
+
+    /// Height above the ground at which the unit's capsule rests.
+    pub fn rest_height(&self) -> f32 {
+        self.height / 2. + self.radius
+    }
+}
+
+#[derive(Default)]
+pub struct UnitDefLoader;
+
+impl AssetLoader for UnitDefLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let mut unit_def: UnitDef = ron::de::from_bytes(bytes)?;
+            unit_def.mesh = load_context.set_labeled_asset("mesh", LoadedAsset::new(Mesh::from(shape::Capsule {
+                radius: unit_def.radius,
+                depth: unit_def.height,
+                ..default()
+            })));
+            unit_def.material = load_context.set_labeled_asset(
+                "material",
+                LoadedAsset::new(StandardMaterial::from(unit_def.color)));
+            load_context.set_default_asset(LoadedAsset::new(unit_def));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["unit.ron"]
+    }
+}
+
+/// Handles to every loaded unit type, keeping them alive.
+#[derive(Resource, Default)]
+pub struct UnitDefs {
+    handles: Vec<HandleUntyped>,
+}
+
+/// Unit type named `name`, if it's loaded.
+pub fn find_unit_def<'a>(unit_defs: &'a Assets<UnitDef>, name: &str) -> Option<(Handle<UnitDef>, &'a UnitDef)> {
+    unit_defs.iter()
+        .find(|(_, unit_def)| unit_def.name == name)
+        .map(|(id, unit_def)| (unit_defs.get_handle(id), unit_def))
+}
+
+/// Name of the unit's type.
+#[derive(Component, Clone, Debug, Reflect)]
+pub struct Unit(pub String);
+
+#[derive(Component, Clone, Copy, Debug, Reflect)]
+pub struct Health {
+    pub current: u32,
+    pub max: u32,
+}
+
+impl Health {
+    pub fn new(max: u32) -> Self {
+        Self { current: max, max }
+    }
+}
+
+/// Spawns a unit of type `unit_def` for `owner`, standing on the ground at `position`.
+pub fn spawn_unit(commands: &mut Commands, unit_def: &UnitDef, position: Vec3, owner: Owner) -> Entity {
+    let mut unit = commands.spawn((
+        PbrBundle {
+            mesh: unit_def.mesh.clone(),
+            material: unit_def.material.clone(),
+            transform: Transform::from_xyz(position.x, unit_def.rest_height(), position.z),
+            ..default()
+        },
+        Collider::capsule(unit_def.height, unit_def.radius),
+        RigidBody::Dynamic,
+        Restitution::new(0.0).with_combine_rule(CoefficientCombine::Min),
+        Friction::new(0.),
+        GravityScale(2.0),
+        LockedAxes::new().lock_rotation_x().lock_rotation_z(),
+        PickableBundle::default(),
+        Selectable,
+        Avoidance::new(unit_def.radius),
+        OrderQueue::default(),
+        unit_def.movement,
+        SupplyCost(unit_def.supply),
+        StuckTracker::default(),
+        MovementPath::default()
+    ));
+    unit.insert((Unit(unit_def.name.clone()), Health::new(unit_def.hp), owner, MatchEntity));
+    if let Some(worker) = unit_def.worker {
+        unit.insert(Worker::new(worker.capacity, worker.harvest_time));
+    }
+    unit.id()
+}
+
+fn load_unit_defs(mut commands: Commands, asset_server: Res<AssetServer>) {
+    match asset_server.load_folder(UNIT_DEFS_FOLDER) {
+        Ok(handles) => commands.insert_resource(UnitDefs { handles }),
+        Err(error) => {
+            error!("Couldn't load unit types from {}: {:?}", UNIT_DEFS_FOLDER, error);
+            commands.init_resource::<UnitDefs>();
+        }
+    }
+}
+
+// Edited movement stats, hit points and supply apply to the units already on the field.
+// Their size, looks and worker capacity only change for the units trained next.
+fn reload_unit_defs(
+    mut asset_events: EventReader<AssetEvent<UnitDef>>,
+    unit_defs: Res<Assets<UnitDef>>,
+    mut units_q: Query<(&Unit, &mut MovementStats, &mut Health, &mut SupplyCost)>,
+) {
+    for event in asset_events.iter() {
+        let AssetEvent::Modified { handle } = event else {
+            continue;
+        };
+        let Some(unit_def) = unit_defs.get(handle) else {
+            continue;
+        };
+        info!("Reloaded unit type {}", unit_def.name);
+        for (_, mut stats, mut health, mut supply_cost) in units_q.iter_mut()
+            .filter(|(unit, ..)| unit.0 == unit_def.name) {
+            *stats = unit_def.movement;
+            health.max = unit_def.hp;
+            health.current = health.current.min(health.max);
+            supply_cost.0 = unit_def.supply;
+        }
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use crate::unit::UnitDef;
+
+    #[test]
+    fn it_parses_the_bundled_unit_types() {
+        let worker = UnitDef::from_ron(include_str!("../assets/units/worker.unit.ron")).unwrap();
+        let soldier = UnitDef::from_ron(include_str!("../assets/units/soldier.unit.ron")).unwrap();
+        assert_eq!(worker.name, "Worker");
+        assert!(worker.worker.is_some());
+        assert_eq!(soldier.name, "Soldier");
+        assert!(soldier.worker.is_none());
+    }
+
+    #[test]
+    fn it_rests_capsule_on_the_ground() {
+        let worker = UnitDef::from_ron(include_str!("../assets/units/worker.unit.ron")).unwrap();
+        assert_eq!(worker.rest_height(), 1.0);
+    }
+}
@@ -1,21 +1,21 @@
 use bevy::prelude::*;
+use bevy::utils::HashMap;
 use bevy::pbr::{DirectionalLight, DirectionalLightBundle, PbrBundle, StandardMaterial};
 use bevy::math::{EulerRot, Quat, Vec3};
 use bevy_mod_picking::prelude::*;
 use bevy_xpbd_3d::components::{Collider, Position};
-use bevy_xpbd_3d::prelude::{CoefficientCombine, Friction, GravityScale, LockedAxes, Restitution, RigidBody};
+use bevy_xpbd_3d::prelude::RigidBody;
 use oxidized_navigation::NavMeshAffector;
-use crate::movement::MovementPath;
-use crate::pathfinding::MoveEvent;
+use crate::building::{Building, BuildingKind};
+use crate::game_state::MatchEntity;
+use crate::harvest::Depot;
+use crate::player::Owner;
+use crate::production::{ProductionQueue, RallyPoint};
+use crate::selection::Selectable;
+use crate::supply::SupplyProvider;
 
-#[derive(Component)]
-pub struct Selected;
-
-pub fn setup_3d_scene(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-) {
+/// Lighting shared by every map; the level itself is spawned from the map file.
+pub fn setup_3d_scene(mut commands: Commands) {
     commands.spawn(DirectionalLightBundle {
         directional_light: DirectionalLight {
             shadows_enabled: true,
@@ -26,59 +26,84 @@ pub fn setup_3d_scene(
         ),
         ..default()
     });
+}
 
-    // Plane
-    commands.spawn((
-        PbrBundle {
-            mesh: meshes.add(Mesh::from(bevy::prelude::shape::Plane {
-                size: 75.0,
-                subdivisions: 0,
-            })),
-            material: materials.add(Color::rgb(0.3, 0.5, 0.3).into()),
-            transform: Transform::IDENTITY,
-            ..default()
-        },
-        RigidBody::Static,
-        Collider::cuboid(75.0, 0.5, 75.0),
-        PickableBundle::default(),
-        NavMeshAffector,
-        On::<Pointer<Down>>::send_event::<MoveEvent>(),
-    ));
+/// Meshes and materials for buildings and their placement ghost.
+#[derive(Resource)]
+pub struct BuildingAssets {
+    handles: HashMap<BuildingKind, (Handle<Mesh>, Handle<StandardMaterial>)>,
+    pub ghost_valid: Handle<StandardMaterial>,
+    pub ghost_invalid: Handle<StandardMaterial>,
+}
 
-    // Cube
-    commands.spawn((
-        PbrBundle {
-            mesh: meshes.add(Mesh::from(shape::Capsule {
-                radius: 0.5,
-                depth: 1.0,
-                ..default()
-            })),
-            material: materials.add(Color::rgb(0.1, 0.1, 0.5).into()),
-            transform: Transform::from_xyz(-5.0, 0.8, -5.0),
+impl BuildingAssets {
+    pub fn new(meshes: &mut Assets<Mesh>, materials: &mut Assets<StandardMaterial>) -> Self {
+        let mut handles = HashMap::new();
+        for (kind, color) in [
+            (BuildingKind::Depot, Color::rgb(0.4, 0.4, 0.45)),
+            (BuildingKind::Barracks, Color::rgb(0.45, 0.3, 0.25)),
+        ] {
+            let size = kind.size();
+            handles.insert(kind, (
+                meshes.add(Mesh::from(shape::Box::new(size.x, size.y, size.z))),
+                materials.add(color.into()),
+            ));
+        }
+        let ghost = |color: Color| StandardMaterial {
+            base_color: color,
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
             ..default()
-        },
-        Collider::capsule(1., 0.5),
-        RigidBody::Dynamic,
-        Restitution::new(0.0).with_combine_rule(CoefficientCombine::Min),
-        Friction::new(0.),
-        GravityScale(2.0),
-        LockedAxes::new().lock_rotation_x().lock_rotation_z(),
-        PickableBundle::default(),
-        Selected,
-        MovementPath::default()
-    ));
+        };
+        Self {
+            handles,
+            ghost_valid: materials.add(ghost(Color::rgba(0.2, 0.9, 0.2, 0.4))),
+            ghost_invalid: materials.add(ghost(Color::rgba(0.9, 0.2, 0.2, 0.4))),
+        }
+    }
 
-    // Thin wall
-    commands.spawn((
+    pub fn get(&self, kind: BuildingKind) -> (Handle<Mesh>, Handle<StandardMaterial>) {
+        self.handles.get(&kind).cloned().unwrap_or_default()
+    }
+}
+
+impl FromWorld for BuildingAssets {
+    fn from_world(world: &mut World) -> Self {
+        world.resource_scope(|world, mut meshes: Mut<Assets<Mesh>>| {
+            Self::new(&mut meshes, &mut world.resource_mut::<Assets<StandardMaterial>>())
+        })
+    }
+}
+
+/// Spawns a building of `kind` for `owner`, standing on the ground at `position`.
+pub fn spawn_building(commands: &mut Commands, assets: &BuildingAssets, kind: BuildingKind, position: Vec3, owner: Owner) -> Entity {
+    let (mesh, material) = assets.get(kind);
+    let size = kind.size();
+    let mut building = commands.spawn((
         PbrBundle {
-            mesh: meshes.add(Mesh::from(bevy::prelude::shape::Cube { size: 0.1 })),
-            material: materials.add(Color::rgb(0.1, 0.1, 0.5).into()),
-            transform: Transform::from_xyz(-3.0, 0.8, 5.0).with_scale(Vec3::new(50.0, 15.0, 1.0)),
+            mesh,
+            material,
+            transform: Transform::from_xyz(position.x, size.y / 2.0, position.z),
             ..default()
         },
-        // At the time of writing, xpbd (v0.2) colliders don't support scaling, so you have to create the collider with the post-scaled size.
         RigidBody::Static,
-        Collider::cuboid(5.0, 1.5, 0.1),
+        Collider::cuboid(size.x, size.y, size.z),
         NavMeshAffector,
+        PickableBundle::default(),
+        Selectable,
+        Building(kind),
+        kind.footprint(),
+        owner,
+        MatchEntity,
     ));
+    if kind == BuildingKind::Depot {
+        building.insert(Depot);
+    }
+    if kind.supply_provided() > 0 {
+        building.insert(SupplyProvider(kind.supply_provided()));
+    }
+    if !kind.trainable().is_empty() {
+        building.insert((ProductionQueue::new(5, kind.trainable()), RallyPoint::default()));
+    }
+    building.id()
 }